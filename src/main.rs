@@ -28,11 +28,19 @@ fn window_conf() -> macroquad::window::Conf {
 }
 
 async fn app() {
-    let mut doc = Document::new();
-    doc.rwl_instance.parse(&read_file(&PathBuf::from("./assets/rwl/test.rwl")).unwrap());
-    doc.rwl_instance.instance().unwrap();
+    let path = env::args()
+        .nth(1)
+        .map_or_else(|| PathBuf::from("./assets/rwl/test.rwl"), PathBuf::from);
     
-    println!("{:?}", doc.rwl_instance.ast);
+    let mut doc = Document::open(path);
+    if let Some(err) = &doc.error {
+        err.emit();
+    }
+    
+    // WATCH=1 reloads the document whenever it changes on disk
+    if env::var("WATCH").is_ok() {
+        doc.watch();
+    }
     
     println!(":3");
     
@@ -45,20 +53,31 @@ async fn app() {
         
         let width = gl_ctx.width();
         let height = gl_ctx.height();
+        let area = Area {
+            a: Vec2(0.0, 0.0),
+            b: Vec2(width, height),
+        };
+        
+        if doc.poll() {
+            if let Some(err) = &doc.error {
+                err.emit();
+            }
+            // force a new layout with the current size
+            last_size = Vec2(0.0, 0.0);
+        }
+        
+        doc.frame(gl_ctx.frame_time());
         
         let mut handle = gl_ctx.begin_drawing();
         
         if last_size != Vec2(width, height) {
-            doc.rwl_instance.update((&mut handle, &theme), &Area {
-                a: Vec2(0.0, 0.0),
-                b: Vec2(width, height),
-            });
+            doc.rwl_instance.update((&mut handle, &theme), &area);
             last_size = Vec2(width, height);
         }
         
         handle.clear_background(Color { r: 0, g: 0, b: 0, a: 255 });
         
-        doc.render(&mut handle);
+        doc.render(&mut handle, &area);
         
         gl_ctx.finish_frame().await;
     }
@@ -74,9 +93,15 @@ async fn main() {
         }
         
         "RTR" => {
+            let path = PathBuf::from("./assets/rtr/test.rtr");
+            
             let mut inst = RTRModule::new();
+            inst.path = Some(path.clone());
             
-            inst.parse(&read_file(&PathBuf::from("./assets/rtr/test.rtr")).unwrap()).unwrap();
+            if let Err(err) = inst.parse(&read_file(&path).unwrap()) {
+                inst.diagnostic(&err).emit();
+                return;
+            }
             
             //println!("{inst:?}");
             
@@ -85,7 +110,7 @@ async fn main() {
             });
             
             if let Err(err) = out {
-                print_error!(LogSource::Rtr, "Err: {err}");
+                inst.diagnostic(&err).emit();
             }
         }
         
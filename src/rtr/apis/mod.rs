@@ -1,9 +1,12 @@
 use crate::rtr::runtime::memory::Memory;
 use crate::rtr::runtime::scope::Scope;
 use crate::rtr::apis::rwl::inject as rwl_inject;
+use crate::rtr::apis::storage::inject as storage_inject;
 
 pub mod rwl;
+pub mod storage;
 
 pub fn inject(memory: &mut Memory, scope: &mut Scope) {
     rwl_inject(memory, scope);
+    storage_inject(memory, scope);
 }
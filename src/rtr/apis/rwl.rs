@@ -24,5 +24,5 @@ pub fn inject(memory: &mut Memory, scope: &mut Scope) {
     //    data
     //});
     
-    //scope.set_var(memory, String::from("document"), ptr);
+    //scope.set_var(memory, scope.symbols.intern("document"), ptr);
 }
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use crate::rtr::RTRModule;
+use crate::rtr::error::Error;
+use crate::rtr::runtime::memory::{MemPointer, Memory};
+use crate::rtr::runtime::scope::Scope;
+use crate::rtr::runtime::shared::SharedValue;
+use crate::rtr::runtime::value::{Function, RustFunction, Value};
+
+// `storage`, which keeps values between runs of the document
+pub fn inject(memory: &mut Memory, scope: &mut Scope) {
+    let methods: [(&str, RustFunction); 5] = [
+        ("get", get),
+        ("set", set),
+        ("remove", remove),
+        ("keys", keys),
+        ("clear", clear)
+    ];
+    
+    let data: HashMap<String, MemPointer> = methods
+        .into_iter()
+        .map(|(name, func)| (String::from(name), memory.alloc(Value::Function(Function::Rust(func)))))
+        .collect();
+    
+    let ptr = memory.alloc(Value::Obj {
+        data,
+        class: None
+    });
+    
+    let name = scope.symbols.intern("storage");
+    scope.set_var(memory, name, ptr);
+}
+
+fn key(vm: &RTRModule, args: &[MemPointer]) -> String {
+    args.first()
+        .map(|ptr| vm.memory.get(*ptr).stringify(&vm.memory))
+        .unwrap_or_default()
+}
+fn failed(reason: String) -> Error {
+    Error::CannotStore { reason, range: None }
+}
+
+fn get(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let key = key(vm, args);
+    let value = vm.storage.borrow_mut().get(&key).map_err(failed)?;
+    
+    Ok(value.unwrap_or(SharedValue::Null).copy_in(&mut vm.memory))
+}
+fn set(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let key = key(vm, args);
+    let value = args.get(1).map_or(SharedValue::Null, |ptr| SharedValue::copy_out(&vm.memory, *ptr));
+    vm.storage.borrow_mut().set(&key, value).map_err(failed)?;
+    
+    Ok(vm.memory.alloc(Value::Null))
+}
+fn remove(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let key = key(vm, args);
+    let data = vm.storage.borrow_mut().remove(&key).map_err(failed)?;
+    
+    Ok(vm.memory.alloc(Value::Bool { data }))
+}
+fn keys(vm: &mut RTRModule, _args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let keys = vm.storage.borrow_mut().keys().map_err(failed)?;
+    let items = keys
+        .into_iter()
+        .map(|data| vm.memory.alloc(Value::Str { data }))
+        .collect();
+    
+    Ok(vm.memory.alloc(Value::Arr { items }))
+}
+fn clear(vm: &mut RTRModule, _args: &[MemPointer]) -> Result<MemPointer, Error> {
+    vm.storage.borrow_mut().clear().map_err(failed)?;
+    
+    Ok(vm.memory.alloc(Value::Null))
+}
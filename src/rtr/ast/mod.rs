@@ -1,3 +1,4 @@
+use std::rc::Rc;
 use crate::shared::position::Position;
 use crate::shared::range::Range;
 use crate::shared::token::Token;
@@ -5,17 +6,18 @@ use crate::shared::token::Token;
 pub mod parser;
 pub mod node;
 
-const SPLIT_CHARS: [char; 29] = [
+const SPLIT_CHARS: [char; 32] = [
     // rtr
     '(',')',
     '[',']',
     '{','}',
-    ',',';',':','=','.','#','~',
+    ',',';',':','=','.','#','~','$',
     '+','-','*','/','%','^',
     '\\','<','>',
     '\'','"','`',
     ' ','\n',
-    '!','?'
+    '!','?',
+    '|','&'
 ];
 
 pub enum CommentType {
@@ -25,6 +27,16 @@ pub enum CommentType {
 }
 
 pub fn tokenise(text: &str) -> Vec<Token> {
+    tokenise_at(text, Position {
+        ln: 1,
+        col: 1,
+        i: 0,
+        script: Rc::from(text)
+    })
+}
+
+// tokenises text that starts at `start` inside a bigger source (like a script in a rwl document)
+pub fn tokenise_at(text: &str, start: Position) -> Vec<Token> {
     macro_rules! add_buf {
         ($buf:expr,$tokens:expr,$pos:expr) => {
                 $tokens.push(Token {
@@ -37,12 +49,7 @@ pub fn tokenise(text: &str) -> Vec<Token> {
         };
     }
     
-    let mut pos: Position = Position {
-        ln: 1,
-        col: 1,
-        i: 0,
-        script: text.to_string()
-    };
+    let mut pos: Position = start;
     
     let mut tokens: Vec<Token> = Vec::new();
     let mut buf: String = String::new();
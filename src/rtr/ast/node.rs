@@ -3,14 +3,26 @@ use crate::shared::range::Range;
 
 #[derive(Debug, Clone)]
 pub struct AstProgram {
-    pub events: Vec<AstTopLevelStatement>
+    pub events: Vec<AstTopLevelStatement>,
+    // everything outside of events, ran once before the first event or when imported
+    pub body: Vec<AstStatement>,
+    // the names in body that importers get
+    pub exports: Vec<String>
 }
 
 #[derive(Debug, Clone)]
 pub enum AstTopLevelStatement {
     Event {
         body: Vec<AstStatement>,
-        target: EventTarget
+        target: EventTarget,
+        // what the args it is ran with are called, `event(name, value) {}`
+        params: Vec<String>
+    },
+    Statement(AstStatement),
+    // `export name := value;` or `export class Name {}`
+    Export {
+        name: String,
+        statement: AstStatement
     }
 }
 
@@ -26,21 +38,71 @@ pub enum AstStatement {
         else_body: Option<Box<AstStatement>>,
         range: Range
     },
+    // runs the first arm whose pattern fits the value
+    Match {
+        value: AstExpression,
+        arms: Vec<MatchArm<AstStatement>>,
+        range: Range
+    },
     ConditionalLoop {
         kind: AstConditionalType,
         cond: AstExpression,
         body: Box<AstStatement>,
+        label: Option<String>,
         range: Range
     },
     Repeat {
         amount: AstExpression,
         body: Box<AstStatement>,
+        label: Option<String>,
         range: Range
     },
     For {
         var: String,
         iterator: AstExpression,
         body: Box<AstStatement>,
+        label: Option<String>,
+        range: Range
+    },
+    
+    Try {
+        body: Box<AstStatement>,
+        // the name the caught value is bound to, if any
+        catch: Option<(Option<String>, Box<AstStatement>)>,
+        finally: Option<Box<AstStatement>>,
+        range: Range
+    },
+    Throw {
+        value: AstExpression,
+        range: Range
+    },
+    Return {
+        value: Option<AstExpression>,
+        range: Range
+    },
+    
+    // `import "path.rtr" as name;`, only at the top level
+    Import {
+        path: String,
+        name: String,
+        range: Range
+    },
+    
+    // methods are funcs taking self first, init is the constructor
+    Class {
+        name: String,
+        parent: Option<AstExpression>,
+        methods: Vec<(String, AstExpression)>,
+        range: Range
+    },
+    
+    // the label picks which loop to leave, otherwise it is the innermost one
+    Break {
+        label: Option<String>,
+        range: Range
+    },
+    Continue {
+        label: Option<String>,
         range: Range
     },
     
@@ -53,7 +115,7 @@ pub enum AstConditionalType {
     Until
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum EventTarget {
     Global {
         name: String
@@ -64,7 +126,7 @@ pub enum EventTarget {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Target {
     Any,
     Id(String),
@@ -80,13 +142,20 @@ pub enum AstExpression {
         range: Range
     },
     CallEvent {
-        target: String
+        target: String,
+        range: Range
     },
     Declare {
         name: String,
         value: Box<AstExpression>,
         range: Range
     },
+    // `[a, b] := arr` or `{x, y} := point`
+    Destructure {
+        pattern: Pattern,
+        value: Box<AstExpression>,
+        range: Range
+    },
     Assignment {
         op: AssignmentOp,
         target: Box<AstExpression>,
@@ -121,12 +190,16 @@ pub enum AstExpression {
         content: String,
         range: Range
     },
+    Template {
+        parts: Vec<TemplatePart>,
+        range: Range
+    },
     Number {
-        content: f32,
+        content: f64,
         range: Range
     },
     Percentage {
-        content: f32,
+        content: f64,
         range: Range
     },
     Func {
@@ -139,7 +212,18 @@ pub enum AstExpression {
         range: Range
     },
     Object {
-        pairs: Vec<(String, AstExpression)>,
+        pairs: Vec<ObjectEntry>,
+        range: Range
+    },
+    // `...value`, only allowed in array and object literals and call args
+    Spread {
+        value: Box<AstExpression>,
+        range: Range
+    },
+    // evaluates to the first fitting arm, or null if none fit
+    Match {
+        value: Box<AstExpression>,
+        arms: Vec<MatchArm<AstExpression>>,
         range: Range
     },
     Color {
@@ -148,6 +232,106 @@ pub enum AstExpression {
     }
 }
 
+impl AstExpression {
+    pub fn range(&self) -> &Range {
+        match self {
+            AstExpression::Call { range, .. }
+            | AstExpression::CallEvent { range, .. }
+            | AstExpression::Declare { range, .. }
+            | AstExpression::Destructure { range, .. }
+            | AstExpression::Assignment { range, .. }
+            | AstExpression::Unary { range, .. }
+            | AstExpression::Binary { range, .. }
+            | AstExpression::Variable { range, .. }
+            | AstExpression::Property { range, .. }
+            | AstExpression::String { range, .. }
+            | AstExpression::Template { range, .. }
+            | AstExpression::Number { range, .. }
+            | AstExpression::Percentage { range, .. }
+            | AstExpression::Func { range, .. }
+            | AstExpression::Array { range, .. }
+            | AstExpression::Object { range, .. }
+            | AstExpression::Spread { range, .. }
+            | AstExpression::Match { range, .. }
+            | AstExpression::Color { range, .. } =>
+                range
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ObjectEntry {
+    Pair(String, AstExpression),
+    Spread(AstExpression)
+}
+
+// what a destructuring declaration binds to
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Name(String),
+    Arr {
+        items: Vec<PatternItem>,
+        // `...name` takes whatever items are left
+        rest: Option<Box<Pattern>>
+    },
+    Obj {
+        // the key to read and what to bind it to, `{key: target}`, or just `{key}`
+        pairs: Vec<(String, PatternItem)>,
+        // `...name` takes the keys that werent named
+        rest: Option<String>
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PatternItem {
+    pub target: Pattern,
+    // used when the value is null, `[a = 1]`
+    pub default: Option<AstExpression>
+}
+
+// `pattern if (guard) => body`, the body is a statement or an expression depending on where the match is
+#[derive(Debug, Clone)]
+pub struct MatchArm<T> {
+    pub pattern: MatchPattern,
+    pub guard: Option<AstExpression>,
+    pub body: T
+}
+
+// what a match arm checks the value against, binding names as it goes
+#[derive(Debug, Clone)]
+pub enum MatchPattern {
+    // `_`
+    Wildcard,
+    // any value, bound to the name
+    Bind(String),
+    // equal to a number, string, bool or null
+    Literal(AstExpression),
+    // `name is type` or `_ is type`, the type is a typeof name or a class the value was made by
+    Type {
+        name: String,
+        bind: Option<String>
+    },
+    // `a | b`
+    Or(Vec<MatchPattern>),
+    // an array of exactly this many items, or at least this many with a rest
+    Arr {
+        items: Vec<MatchPattern>,
+        rest: Option<Box<MatchPattern>>
+    },
+    // an object with at least these keys
+    Obj {
+        pairs: Vec<(String, MatchPattern)>,
+        rest: Option<Box<MatchPattern>>
+    }
+}
+
+// a piece of a backtick string, either raw text or an interpolated ${expr}
+#[derive(Debug, Clone)]
+pub enum TemplatePart {
+    Str(String),
+    Expr(AstExpression)
+}
+
 #[derive(Debug, Clone)]
 pub enum UnaryOp {
     Minus,
@@ -173,7 +357,11 @@ pub enum BinaryOp {
     BiggerEql,
     SmallerEql,
     
-    NullishCoalescence
+    NullishCoalescence,
+    
+    // short circuiting, these evaluate to whichever side decided the result
+    And,
+    Or
 }
 #[derive(Debug, Clone)]
 pub enum AssignmentOp {
@@ -186,7 +374,9 @@ pub enum AssignmentOp {
     Mod,
     Pow,
     
-    NullishCoalescence
+    NullishCoalescence,
+    And,
+    Or
 }
 impl From<AssignmentOp> for BinaryOp {
     fn from(value: AssignmentOp) -> Self {
@@ -199,15 +389,13 @@ impl From<AssignmentOp> for BinaryOp {
             AssignmentOp::Mod => BinaryOp::Mod,
             AssignmentOp::Pow => BinaryOp::Pow,
             
-            AssignmentOp::NullishCoalescence => BinaryOp::NullishCoalescence
+            AssignmentOp::NullishCoalescence => BinaryOp::NullishCoalescence,
+            AssignmentOp::And => BinaryOp::And,
+            AssignmentOp::Or => BinaryOp::Or
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Parameter {
-    pub name: String
-}
 #[derive(Debug, Clone)]
 pub enum PropertyKey {
     Str(String),
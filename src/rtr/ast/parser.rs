@@ -1,4 +1,4 @@
-use crate::rtr::ast::node::{AssignmentOp, AstConditionalType, AstExpression, AstProgram, AstStatement, AstTopLevelStatement, BinaryOp, EventTarget, PropertyKey, Target, UnaryOp};
+use crate::rtr::ast::node::{AssignmentOp, AstConditionalType, AstExpression, AstProgram, AstStatement, AstTopLevelStatement, BinaryOp, EventTarget, MatchArm, MatchPattern, ObjectEntry, Pattern, PatternItem, PropertyKey, Target, TemplatePart, UnaryOp};
 use crate::rtr::error::Error;
 use crate::shared::color::parse_hex_color;
 use crate::shared::range::Range;
@@ -92,10 +92,11 @@ impl Parser {
             })
         }
     }
+    // a piece of a number literal, checked properly once the whole literal is read
     fn expect_num(&mut self) -> Maybe<String> {
         let tkn = self.consume();
         
-        if let TokenType::Text(txt) = &tkn.token_type && is_numeric(txt) {
+        if let TokenType::Text(txt) = &tkn.token_type && starts_numeric(txt) {
             Ok(txt.clone())
         } else {
             Err(Error::ExpectedText {
@@ -108,21 +109,32 @@ impl Parser {
     // top level
     pub fn parse(&mut self) -> Maybe<AstProgram> {
         let mut events: Vec<AstTopLevelStatement> = Vec::new();
+        let mut body: Vec<AstStatement> = Vec::new();
+        let mut exports: Vec<String> = Vec::new();
         
         while !self.at_end() {
             let statement = self.top_level_statement()?;
             
-            match &statement {
+            match statement {
                 AstTopLevelStatement::Event { .. } => {
                     events.push(statement);
                 }
+                AstTopLevelStatement::Statement(statement) => {
+                    body.push(statement);
+                }
+                AstTopLevelStatement::Export { name, statement } => {
+                    exports.push(name);
+                    body.push(statement);
+                }
             }
             self.consume_whitespace();
         }
         
         Ok(
             AstProgram {
-                events
+                events,
+                body,
+                exports
             }
         )
     }
@@ -134,14 +146,57 @@ impl Parser {
         if self.peek() == "event" {
             return self.event();
         }
+        if self.peek() == "import" {
+            return self.import();
+        }
+        if self.peek() == "export" {
+            return self.export();
+        }
         
-        // TODO: top level decls
-        Err(
-            Error::UnexpectedToken {
-                token: Box::new(self.peek()),
-                range: Box::new(self.peek().range)
-            }
-        )
+        Ok(AstTopLevelStatement::Statement(self.statement()?))
+    }
+    
+    fn import(&mut self) -> Maybe<AstTopLevelStatement> {
+        let start = self.get_next_start();
+        
+        self.expect("import")?;
+        self.consume_whitespace();
+        let path = match self.str()? {
+            AstExpression::String { content, .. } => content,
+            other => return Err(Error::UnexpectedToken {
+                token: Box::new(self.tokens[self.pointer - 1].clone()),
+                range: Box::new(other.range().clone())
+            })
+        };
+        self.consume_whitespace();
+        self.expect("as")?;
+        self.consume_whitespace();
+        let name = self.expect_text()?;
+        let range = Range { start, end: self.get_last_end() };
+        
+        self.consume_whitespace();
+        self.expect(TokenType::SemiColon)?;
+        
+        Ok(AstTopLevelStatement::Statement(AstStatement::Import { path, name, range }))
+    }
+    fn export(&mut self) -> Maybe<AstTopLevelStatement> {
+        let start = self.get_next_start();
+        
+        self.expect("export")?;
+        self.consume_whitespace();
+        let statement = self.statement()?;
+        
+        let name = match &statement {
+            AstStatement::Expression(AstExpression::Declare { name, .. })
+            | AstStatement::Class { name, .. } =>
+                name.clone(),
+            
+            _ => return Err(Error::InvalidExport {
+                range: Box::new(Range { start, end: self.get_last_end() })
+            })
+        };
+        
+        Ok(AstTopLevelStatement::Export { name, statement })
     }
     
     fn event(&mut self) -> Maybe<AstTopLevelStatement> {
@@ -150,13 +205,23 @@ impl Parser {
         self.expect(TokenType::OpenParen)?;
         let target = self.event_target()?;
         self.consume_whitespace();
+        
+        let mut params = Vec::new();
+        while self.peek() == TokenType::Comma {
+            self.consume();
+            self.consume_whitespace();
+            params.push(self.expect_text()?);
+            self.consume_whitespace();
+        }
+        
         self.expect(TokenType::CloseParen)?;
         self.consume_whitespace();
         let body = self.block()?;
         
         Ok(AstTopLevelStatement::Event {
             body,
-            target
+            target,
+            params
         })
     }
     
@@ -235,24 +300,47 @@ impl Parser {
         
         let tkn = self.peek();
         
-        if tkn == TokenType::OpenCurly {
+        if tkn == TokenType::OpenCurly && !self.at_destructure() {
             let body = self.block()?;
             return Ok(AstStatement::Block {
                 body
             });
         }
+        
+        // loops can be labelled so break and continue can target them, `outer: while (...) {}`
+        let label = self.loop_label();
+        let tkn = self.peek();
+        
         if let TokenType::Text(name) = tkn.token_type {
             if name.as_str() == "if" {
                 return self.branch();
             }
             if name.as_str() == "while" || name.as_str() == "until" {
-                return self.conditional_loop();
+                return self.conditional_loop(label);
             }
             if name.as_str() == "repeat" {
-                return self.repeat();
+                return self.repeat(label);
             }
             if name.as_str() == "for" {
-                return self.for_loop();
+                return self.for_loop(label);
+            }
+            if name.as_str() == "break" || name.as_str() == "continue" {
+                return self.loop_control();
+            }
+            if name.as_str() == "try" {
+                return self.try_catch();
+            }
+            if name.as_str() == "throw" {
+                return self.throw();
+            }
+            if name.as_str() == "return" {
+                return self.return_statement();
+            }
+            if name.as_str() == "class" {
+                return self.class();
+            }
+            if name.as_str() == "match" {
+                return self.match_statement();
             }
         }
         
@@ -261,6 +349,46 @@ impl Parser {
         Ok(AstStatement::Expression(expr))
     }
     
+    fn loop_label(&mut self) -> Option<String> {
+        let ptr = self.pointer;
+        
+        if let TokenType::Text(label) = self.peek().token_type && self.peek_amount(2) == TokenType::Colon {
+            self.consume();
+            self.consume();
+            self.consume_whitespace();
+            
+            if ["while", "until", "repeat", "for"].iter().any(|keyword| self.peek() == *keyword) {
+                return Some(label);
+            }
+        }
+        
+        self.pointer = ptr;
+        None
+    }
+    fn loop_control(&mut self) -> Maybe<AstStatement> {
+        let start = self.get_next_start();
+        
+        let keyword = self.consume();
+        
+        let mut label = None;
+        if self.peek() == TokenType::Space {
+            self.consume_whitespace();
+            if self.peek() != TokenType::SemiColon {
+                label = Some(self.expect_text()?);
+            }
+        }
+        let range = Range { start, end: self.get_last_end() };
+        
+        self.consume_whitespace();
+        self.expect(TokenType::SemiColon)?;
+        
+        Ok(if keyword == "break" {
+            AstStatement::Break { label, range }
+        } else {
+            AstStatement::Continue { label, range }
+        })
+    }
+    
     fn branch(&mut self) -> Maybe<AstStatement> {
         let start = self.get_next_start();
         
@@ -306,7 +434,325 @@ impl Parser {
             range: Range { start, end: self.get_last_end() }
         })
     }
-    fn conditional_loop(&mut self) -> Maybe<AstStatement> {
+    fn try_catch(&mut self) -> Maybe<AstStatement> {
+        let start = self.get_next_start();
+        
+        self.expect("try")?;
+        self.consume_whitespace();
+        let body = Box::new(self.statement()?);
+        
+        self.consume_whitespace();
+        let mut catch = None;
+        if self.peek() == "catch" {
+            self.consume();
+            self.consume_whitespace();
+            
+            let mut name = None;
+            if self.peek() == TokenType::OpenParen {
+                self.consume();
+                self.consume_whitespace();
+                name = Some(self.expect_text()?);
+                self.consume_whitespace();
+                self.expect(TokenType::CloseParen)?;
+                self.consume_whitespace();
+            }
+            
+            catch = Some((name, Box::new(self.statement()?)));
+            self.consume_whitespace();
+        }
+        
+        let mut finally = None;
+        if self.peek() == "finally" || catch.is_none() {
+            self.expect_multiple(vec!["catch".into(), "finally".into()])?;
+            self.consume_whitespace();
+            finally = Some(Box::new(self.statement()?));
+        }
+        
+        Ok(AstStatement::Try {
+            body,
+            catch,
+            finally,
+            range: Range { start, end: self.get_last_end() }
+        })
+    }
+    fn throw(&mut self) -> Maybe<AstStatement> {
+        let start = self.get_next_start();
+        
+        self.expect("throw")?;
+        self.consume_whitespace();
+        let value = self.expression()?;
+        let range = Range { start, end: self.get_last_end() };
+        
+        self.consume_whitespace();
+        self.expect(TokenType::SemiColon)?;
+        
+        Ok(AstStatement::Throw { value, range })
+    }
+    fn return_statement(&mut self) -> Maybe<AstStatement> {
+        let start = self.get_next_start();
+        
+        self.expect("return")?;
+        self.consume_whitespace();
+        let value = if self.peek() == TokenType::SemiColon {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let range = Range { start, end: self.get_last_end() };
+        
+        self.consume_whitespace();
+        self.expect(TokenType::SemiColon)?;
+        
+        Ok(AstStatement::Return { value, range })
+    }
+    
+    fn class(&mut self) -> Maybe<AstStatement> {
+        let start = self.get_next_start();
+        
+        self.expect("class")?;
+        self.consume_whitespace();
+        let name = self.expect_text()?;
+        self.consume_whitespace();
+        
+        let mut parent = None;
+        if self.peek() == "extends" {
+            self.consume();
+            self.consume_whitespace();
+            parent = Some(self.expression()?);
+            self.consume_whitespace();
+        }
+        
+        self.expect(TokenType::OpenCurly)?;
+        self.consume_whitespace();
+        
+        let mut methods = Vec::new();
+        while !(self.peek() == TokenType::CloseCurly || self.at_end()) {
+            let method_start = self.get_next_start();
+            let method = self.expect_text()?;
+            self.consume_whitespace();
+            
+            let mut params = vec![String::from("self")];
+            self.expect(TokenType::OpenParen)?;
+            self.consume_whitespace();
+            while !(self.peek() == TokenType::CloseParen || self.at_end()) {
+                params.push(self.expect_text()?);
+                self.consume_whitespace();
+                
+                if self.peek() != TokenType::CloseParen {
+                    self.expect(TokenType::Comma)?;
+                    self.consume_whitespace();
+                }
+            }
+            self.expect(TokenType::CloseParen)?;
+            
+            let body = self.statement()?;
+            methods.push((method, AstExpression::Func {
+                params,
+                body: Box::new(body),
+                range: Range { start: method_start, end: self.get_last_end() }
+            }));
+            self.consume_whitespace();
+        }
+        self.expect(TokenType::CloseCurly)?;
+        
+        Ok(AstStatement::Class {
+            name,
+            parent,
+            methods,
+            range: Range { start, end: self.get_last_end() }
+        })
+    }
+    
+    fn match_statement(&mut self) -> Maybe<AstStatement> {
+        let start = self.get_next_start();
+        
+        let value = self.match_value()?;
+        let arms = self.match_arms(Self::statement)?;
+        
+        Ok(AstStatement::Match {
+            value,
+            arms,
+            range: Range { start, end: self.get_last_end() }
+        })
+    }
+    fn match_expression(&mut self) -> Maybe<AstExpression> {
+        let start = self.get_next_start();
+        
+        let value = self.match_value()?;
+        let arms = self.match_arms(Self::expression)?;
+        
+        Ok(AstExpression::Match {
+            value: Box::new(value),
+            arms,
+            range: Range { start, end: self.get_last_end() }
+        })
+    }
+    // `match (value)`
+    fn match_value(&mut self) -> Maybe<AstExpression> {
+        self.expect("match")?;
+        self.consume_whitespace();
+        self.expect(TokenType::OpenParen)?;
+        self.consume_whitespace();
+        let value = self.expression()?;
+        self.consume_whitespace();
+        self.expect(TokenType::CloseParen)?;
+        self.consume_whitespace();
+        
+        Ok(value)
+    }
+    fn match_arms<T>(&mut self, body: fn(&mut Self) -> Maybe<T>) -> Maybe<Vec<MatchArm<T>>> {
+        self.expect(TokenType::OpenCurly)?;
+        self.consume_whitespace();
+        
+        let mut arms = Vec::new();
+        while !(self.peek() == TokenType::CloseCurly || self.at_end()) {
+            let pattern = self.match_pattern()?;
+            self.consume_whitespace();
+            
+            let mut guard = None;
+            if self.peek() == "if" {
+                self.consume();
+                self.consume_whitespace();
+                self.expect(TokenType::OpenParen)?;
+                self.consume_whitespace();
+                guard = Some(self.expression()?);
+                self.consume_whitespace();
+                self.expect(TokenType::CloseParen)?;
+                self.consume_whitespace();
+            }
+            
+            self.expect(TokenType::Equal)?;
+            self.expect(TokenType::RightArrow)?;
+            self.consume_whitespace();
+            
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body: body(self)?
+            });
+            self.consume_whitespace();
+            
+            // arms can be split by commas, which expression arms need to end on one line
+            if self.peek() == TokenType::Comma {
+                self.consume();
+                self.consume_whitespace();
+            }
+        }
+        self.expect(TokenType::CloseCurly)?;
+        
+        Ok(arms)
+    }
+    fn match_pattern(&mut self) -> Maybe<MatchPattern> {
+        let first = self.single_match_pattern()?;
+        self.consume_whitespace();
+        
+        if self.peek() != TokenType::Pipe {
+            return Ok(first);
+        }
+        
+        let mut options = vec![first];
+        while self.peek() == TokenType::Pipe {
+            self.consume();
+            self.consume_whitespace();
+            options.push(self.single_match_pattern()?);
+            self.consume_whitespace();
+        }
+        
+        Ok(MatchPattern::Or(options))
+    }
+    fn single_match_pattern(&mut self) -> Maybe<MatchPattern> {
+        match self.peek().token_type {
+            TokenType::OpenSquare => {
+                self.consume();
+                self.consume_whitespace();
+                
+                let mut items = Vec::new();
+                let mut rest = None;
+                while !(self.peek() == TokenType::CloseSquare || self.at_end()) {
+                    if self.at_spread() {
+                        self.consume_spread();
+                        rest = Some(Box::new(self.match_pattern()?));
+                        self.consume_whitespace();
+                        break;
+                    }
+                    
+                    items.push(self.match_pattern()?);
+                    self.consume_whitespace();
+                    
+                    if self.peek() != TokenType::CloseSquare {
+                        self.expect(TokenType::Comma)?;
+                        self.consume_whitespace();
+                    }
+                }
+                self.expect(TokenType::CloseSquare)?;
+                
+                Ok(MatchPattern::Arr { items, rest })
+            }
+            TokenType::OpenCurly => {
+                self.consume();
+                self.consume_whitespace();
+                
+                let mut pairs = Vec::new();
+                let mut rest = None;
+                while !(self.peek() == TokenType::CloseCurly || self.at_end()) {
+                    if self.at_spread() {
+                        self.consume_spread();
+                        rest = Some(Box::new(self.match_pattern()?));
+                        self.consume_whitespace();
+                        break;
+                    }
+                    
+                    let key = self.expect_text()?;
+                    self.consume_whitespace();
+                    let pattern = if self.peek() == TokenType::Colon {
+                        self.consume();
+                        self.consume_whitespace();
+                        self.match_pattern()?
+                    } else {
+                        MatchPattern::Bind(key.clone())
+                    };
+                    pairs.push((key, pattern));
+                    self.consume_whitespace();
+                    
+                    if self.peek() != TokenType::CloseCurly {
+                        self.expect(TokenType::Comma)?;
+                        self.consume_whitespace();
+                    }
+                }
+                self.expect(TokenType::CloseCurly)?;
+                
+                Ok(MatchPattern::Obj { pairs, rest })
+            }
+            TokenType::Quote | TokenType::DoubleQuote | TokenType::BackQuote | TokenType::Minus =>
+                Ok(MatchPattern::Literal(self.unary()?)),
+            
+            TokenType::Text(name) if starts_numeric(&name) =>
+                Ok(MatchPattern::Literal(self.unary()?)),
+            TokenType::Text(name) if ["true", "false", "null"].contains(&name.as_str()) =>
+                Ok(MatchPattern::Literal(self.primary()?)),
+            
+            _ => {
+                let name = self.expect_text()?;
+                let bind = (name != "_").then_some(name);
+                
+                let ptr = self.pointer;
+                self.consume_whitespace();
+                if self.peek() == "is" {
+                    self.consume();
+                    self.consume_whitespace();
+                    return Ok(MatchPattern::Type {
+                        name: self.expect_text()?,
+                        bind
+                    });
+                }
+                self.pointer = ptr;
+                
+                Ok(bind.map_or(MatchPattern::Wildcard, MatchPattern::Bind))
+            }
+        }
+    }
+    
+    fn conditional_loop(&mut self, label: Option<String>) -> Maybe<AstStatement> {
         let start = self.get_next_start();
         
         let token = self.expect_multiple(vec!["while".into(), "until".into()])?;
@@ -332,10 +778,11 @@ impl Parser {
             kind,
             cond,
             body: Box::new(body),
+            label,
             range: Range { start, end: self.get_last_end() }
         })
     }
-    fn repeat(&mut self) -> Maybe<AstStatement> {
+    fn repeat(&mut self, label: Option<String>) -> Maybe<AstStatement> {
         let start = self.get_next_start();
         
         self.expect("repeat")?;
@@ -352,10 +799,11 @@ impl Parser {
         Ok(AstStatement::Repeat {
             amount,
             body: Box::new(body),
+            label,
             range: Range { start, end: self.get_last_end() }
         })
     }
-    fn for_loop(&mut self) -> Maybe<AstStatement> {
+    fn for_loop(&mut self, label: Option<String>) -> Maybe<AstStatement> {
         let start = self.get_next_start();
         
         self.expect("for")?;
@@ -377,6 +825,7 @@ impl Parser {
             var,
             iterator,
             body: Box::new(body),
+            label,
             range: Range { start, end: self.get_last_end() }
         })
     }
@@ -408,11 +857,158 @@ impl Parser {
             self.pointer = ptr;
         }
         
+        // only a destructuring declaration if the pattern is followed by :=, otherwise its a literal
+        if [TokenType::OpenSquare, TokenType::OpenCurly].contains(&self.peek().token_type) {
+            let ptr = self.pointer;
+            if let Ok(pattern) = self.pattern() {
+                self.consume_whitespace();
+                if self.peek() == TokenType::Colon && self.peek_amount(2) == TokenType::Equal {
+                    self.consume();
+                    self.consume();
+                    self.consume_whitespace();
+                    
+                    let val = self.expression()?;
+                    
+                    return Ok(AstExpression::Destructure {
+                        pattern,
+                        value: Box::new(val),
+                        range: Range { start, end: self.get_last_end() }
+                    });
+                }
+            }
+            self.pointer = ptr;
+        }
+        
         self.assignment()
     }
+    // whether a pattern followed by := is next, so `{x, y} := point;` isnt read as a block
+    fn at_destructure(&mut self) -> bool {
+        let ptr = self.pointer;
+        let is = self.pattern().is_ok() && {
+            self.consume_whitespace();
+            self.peek() == TokenType::Colon && self.peek_amount(2) == TokenType::Equal
+        };
+        self.pointer = ptr;
+        is
+    }
+    fn pattern(&mut self) -> Maybe<Pattern> {
+        match self.peek().token_type {
+            TokenType::OpenSquare => {
+                self.consume();
+                self.consume_whitespace();
+                
+                let mut items = Vec::new();
+                let mut rest = None;
+                while !(self.peek() == TokenType::CloseSquare || self.at_end()) {
+                    if self.at_spread() {
+                        self.consume_spread();
+                        rest = Some(Box::new(self.pattern()?));
+                        self.consume_whitespace();
+                        break;
+                    }
+                    
+                    items.push(self.pattern_item()?);
+                    self.consume_whitespace();
+                    
+                    if self.peek() != TokenType::CloseSquare {
+                        self.expect(TokenType::Comma)?;
+                        self.consume_whitespace();
+                    }
+                }
+                self.expect(TokenType::CloseSquare)?;
+                
+                Ok(Pattern::Arr { items, rest })
+            }
+            TokenType::OpenCurly => {
+                self.consume();
+                self.consume_whitespace();
+                
+                let mut pairs = Vec::new();
+                let mut rest = None;
+                while !(self.peek() == TokenType::CloseCurly || self.at_end()) {
+                    if self.at_spread() {
+                        self.consume_spread();
+                        rest = Some(self.expect_text()?);
+                        self.consume_whitespace();
+                        break;
+                    }
+                    
+                    let key = self.expect_text()?;
+                    self.consume_whitespace();
+                    let item = if self.peek() == TokenType::Colon {
+                        self.consume();
+                        self.consume_whitespace();
+                        self.pattern_item()?
+                    } else {
+                        PatternItem {
+                            target: Pattern::Name(key.clone()),
+                            default: self.pattern_default()?
+                        }
+                    };
+                    pairs.push((key, item));
+                    self.consume_whitespace();
+                    
+                    if self.peek() != TokenType::CloseCurly {
+                        self.expect(TokenType::Comma)?;
+                        self.consume_whitespace();
+                    }
+                }
+                self.expect(TokenType::CloseCurly)?;
+                
+                Ok(Pattern::Obj { pairs, rest })
+            }
+            
+            _ => Ok(Pattern::Name(self.expect_text()?))
+        }
+    }
+    fn pattern_item(&mut self) -> Maybe<PatternItem> {
+        let target = self.pattern()?;
+        self.consume_whitespace();
+        
+        Ok(PatternItem {
+            target,
+            default: self.pattern_default()?
+        })
+    }
+    // `= value`, but not `==`
+    fn pattern_default(&mut self) -> Maybe<Option<AstExpression>> {
+        self.consume_whitespace();
+        if self.peek() != TokenType::Equal || self.peek_amount(2) == TokenType::Equal {
+            return Ok(None);
+        }
+        
+        self.consume();
+        self.consume_whitespace();
+        Ok(Some(self.expression()?))
+    }
+    
+    fn at_spread(&self) -> bool {
+        (1..=3).all(|i| self.peek_amount(i) == TokenType::Period)
+    }
+    fn consume_spread(&mut self) {
+        for _ in 0..3 {
+            self.consume();
+        }
+        self.consume_whitespace();
+    }
+    // an item of an array or call that might be spread
+    fn item(&mut self) -> Maybe<AstExpression> {
+        if !self.at_spread() {
+            return self.expression();
+        }
+        
+        let start = self.get_next_start();
+        self.consume_spread();
+        let value = self.expression()?;
+        
+        Ok(AstExpression::Spread {
+            value: Box::new(value),
+            range: Range { start, end: self.get_last_end() }
+        })
+    }
     fn assignment(&mut self) -> Maybe<AstExpression> {
         let start = self.get_next_start();
-        let expr = self.equality()?;
+        let expr = self.logical_or()?;
         self.consume_whitespace();
         
         let mut op = None;
@@ -424,6 +1020,17 @@ impl Parser {
             op = Some(AssignmentOp::NullishCoalescence);
         }
         
+        if matches!(self.peek().token_type, TokenType::Ampersand | TokenType::Pipe)
+        && self.peek_amount(2) == self.peek().token_type
+        && self.peek_amount(3) == TokenType::Equal
+        {
+            op = Some(if self.peek() == TokenType::Ampersand {
+                AssignmentOp::And
+            } else {
+                AssignmentOp::Or
+            });
+        }
+        
         if self.peek_amount(2) == TokenType::Equal && matches!(self.peek().token_type,
             TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash | TokenType::Mod | TokenType::Pow
         ) {
@@ -446,7 +1053,9 @@ impl Parser {
         if let Some(op) = op {
             let op_len: usize = match op {
                 AssignmentOp::Default => 1,
-                AssignmentOp::NullishCoalescence => 3,
+                AssignmentOp::NullishCoalescence
+                | AssignmentOp::And
+                | AssignmentOp::Or => 3,
                 _ => 2
             };
             
@@ -468,6 +1077,73 @@ impl Parser {
         
         Ok(expr)
     }
+    // `&&`/`and` or `||`/`or`, along with how many tokens it takes up
+    fn logical_op(&self) -> Option<(BinaryOp, usize)> {
+        let tkn = self.peek();
+        
+        match &tkn.token_type {
+            TokenType::Ampersand | TokenType::Pipe
+                if self.peek_amount(2) == tkn.token_type && self.peek_amount(3) != TokenType::Equal =>
+                Some((if tkn == TokenType::Ampersand { BinaryOp::And } else { BinaryOp::Or }, 2)),
+            
+            TokenType::Text(text) if text == "and" =>
+                Some((BinaryOp::And, 1)),
+            TokenType::Text(text) if text == "or" =>
+                Some((BinaryOp::Or, 1)),
+            
+            _ => None
+        }
+    }
+    fn logical_or(&mut self) -> Maybe<AstExpression> {
+        let start = self.get_next_start();
+        let mut expr = self.logical_and()?;
+        
+        self.consume_whitespace();
+        
+        while let Some((BinaryOp::Or, len)) = self.logical_op() {
+            for _i in 0..len {
+                self.consume();
+            }
+            
+            self.consume_whitespace();
+            
+            expr = AstExpression::Binary {
+                left: Box::new(expr),
+                right: Box::new(self.logical_and()?),
+                op: BinaryOp::Or,
+                range: Range { start: start.clone(), end: self.get_last_end() }
+            };
+            
+            self.consume_whitespace();
+        }
+        
+        Ok(expr)
+    }
+    fn logical_and(&mut self) -> Maybe<AstExpression> {
+        let start = self.get_next_start();
+        let mut expr = self.equality()?;
+        
+        self.consume_whitespace();
+        
+        while let Some((BinaryOp::And, len)) = self.logical_op() {
+            for _i in 0..len {
+                self.consume();
+            }
+            
+            self.consume_whitespace();
+            
+            expr = AstExpression::Binary {
+                left: Box::new(expr),
+                right: Box::new(self.equality()?),
+                op: BinaryOp::And,
+                range: Range { start: start.clone(), end: self.get_last_end() }
+            };
+            
+            self.consume_whitespace();
+        }
+        
+        Ok(expr)
+    }
     fn equality(&mut self) -> Maybe<AstExpression> {
         let start = self.get_next_start();
         let mut expr = self.comparison()?;
@@ -673,20 +1349,33 @@ impl Parser {
                 self.consume_whitespace();
                 self.expect(TokenType::CloseParen)?;
                 return Ok(AstExpression::CallEvent {
-                    target
+                    target,
+                    range: Range { start, end: self.get_last_end() }
                 })
             }
         
         let mut expr = self.func()?;
         self.consume_whitespace();
-        if self.peek() == TokenType::OpenParen {
+        
+        // calls and properties can chain, `text.split(" ").map(f)[0]`
+        while [TokenType::OpenParen, TokenType::Period, TokenType::OpenSquare].contains(&self.peek().token_type) {
+            if self.peek() != TokenType::OpenParen {
+                let key = self.property_key()?;
+                expr = AstExpression::Property {
+                    obj: Box::new(expr),
+                    key,
+                    range: Range { start: start.clone(), end: self.get_last_end() }
+                };
+                self.consume_whitespace();
+                continue;
+            }
             self.consume();
             
             let mut args = Vec::new();
             
             self.consume_whitespace();
             while !(self.peek() == TokenType::CloseParen || self.at_end()) {
-                let expression = self.expression();
+                let expression = self.item();
                 self.consume_whitespace();
                 
                 match expression {
@@ -709,8 +1398,9 @@ impl Parser {
             expr = AstExpression::Call {
                 args,
                 func: Box::new(expr),
-                range: Range { start, end: self.get_last_end() }
+                range: Range { start: start.clone(), end: self.get_last_end() }
             };
+            self.consume_whitespace();
         }
         
         Ok(expr)
@@ -778,18 +1468,7 @@ impl Parser {
         self.consume_whitespace();
         
         while self.peek() == TokenType::Period || self.peek() == TokenType::OpenSquare {
-            let key = match self.consume().token_type {
-                TokenType::Period => {
-                    PropertyKey::Str(self.expect_text()?)
-                }
-                TokenType::OpenSquare => {
-                    let expr = self.expression()?;
-                    self.expect(TokenType::CloseSquare)?;
-                    PropertyKey::Expr(Box::new(expr))
-                }
-                
-                _ => panic!()
-            };
+            let key = self.property_key()?;
             
             expr = AstExpression::Property {
                 obj: Box::new(expr),
@@ -800,6 +1479,21 @@ impl Parser {
         
         Ok(expr)
     }
+    // `.name` or `[expr]`
+    fn property_key(&mut self) -> Maybe<PropertyKey> {
+        Ok(match self.consume().token_type {
+            TokenType::Period => {
+                PropertyKey::Str(self.expect_text()?)
+            }
+            TokenType::OpenSquare => {
+                let expr = self.expression()?;
+                self.expect(TokenType::CloseSquare)?;
+                PropertyKey::Expr(Box::new(expr))
+            }
+            
+            _ => panic!()
+        })
+    }
     fn primary(&mut self) -> Maybe<AstExpression> {
         if self.peek() == TokenType::OpenParen {
             self.consume();
@@ -820,8 +1514,10 @@ impl Parser {
             return self.str();
         }
         if let TokenType::Text(name) = self.peek().token_type {
-            if is_numeric(&name) {
+            if starts_numeric(&name) {
                 return self.num();
+            } else if name == "match" {
+                return self.match_expression();
             } else if is_alpha(&name) {
                 let range = self.consume().range;
                 return Ok(AstExpression::Variable { name, range });
@@ -843,7 +1539,7 @@ impl Parser {
         
         let mut items = Vec::new();
         while !(self.peek() == TokenType::CloseSquare || self.at_end()) {
-            items.push(self.expression()?);
+            items.push(self.item()?);
             
             if self.peek() != TokenType::CloseSquare {
                 self.expect_multiple(vec![
@@ -871,11 +1567,16 @@ impl Parser {
         
         let mut pairs = Vec::new();
         while !(self.peek() == TokenType::CloseCurly || self.at_end()) {
-            let name = self.expect_text()?;
-            self.consume_whitespace();
-            self.expect(TokenType::Colon)?;
-            self.consume_whitespace();
-            pairs.push((name, self.expression()?));
+            if self.at_spread() {
+                self.consume_spread();
+                pairs.push(ObjectEntry::Spread(self.expression()?));
+            } else {
+                let name = self.expect_text()?;
+                self.consume_whitespace();
+                self.expect(TokenType::Colon)?;
+                self.consume_whitespace();
+                pairs.push(ObjectEntry::Pair(name, self.expression()?));
+            }
             
             if self.peek() != TokenType::CloseCurly {
                 self.expect_multiple(vec![
@@ -921,6 +1622,10 @@ impl Parser {
             TokenType::DoubleQuote,
             TokenType::BackQuote
         ])?.token_type;
+        if quote == TokenType::BackQuote {
+            return self.template(start);
+        }
+        
         let mut content = String::new();
         while !(self.peek() == quote || self.at_end()) {
             content = format!("{}{}", content, self.consume());
@@ -932,16 +1637,77 @@ impl Parser {
             range: Range { start, end: self.get_last_end() }
         })
     }
+    fn template(&mut self, start: Position) -> Maybe<AstExpression> {
+        let mut parts = Vec::new();
+        let mut content = String::new();
+        while !(self.peek() == TokenType::BackQuote || self.at_end()) {
+            if self.peek() == TokenType::Dollar && self.peek_amount(2) == TokenType::OpenCurly {
+                self.consume();
+                self.consume();
+                
+                if !content.is_empty() {
+                    parts.push(TemplatePart::Str(std::mem::take(&mut content)));
+                }
+                
+                self.consume_whitespace();
+                parts.push(TemplatePart::Expr(self.expression()?));
+                self.consume_whitespace();
+                self.expect(TokenType::CloseCurly)?;
+                continue;
+            }
+            content = format!("{}{}", content, self.consume());
+        }
+        self.expect(TokenType::BackQuote)?;
+        
+        let range = Range { start, end: self.get_last_end() };
+        
+        // without any interpolation its just a normal string
+        if !parts.iter().any(|part| matches!(part, TemplatePart::Expr(_))) {
+            return Ok(AstExpression::String {
+                content,
+                range
+            });
+        }
+        
+        if !content.is_empty() {
+            parts.push(TemplatePart::Str(content));
+        }
+        
+        Ok(AstExpression::Template {
+            parts,
+            range
+        })
+    }
     fn num(&mut self) -> Maybe<AstExpression> {
         let start = self.get_next_start();
         
         let mut val = self.expect_num()?;
+        let is_radix = radix_of(&val).is_some();
         
-        if self.peek() == TokenType::Period {
-            self.consume();
-            val = format!("{}.{}", val, self.expect_num()?);
-        }
+        if !is_radix
+            && self.peek() == TokenType::Period
+            && let TokenType::Text(frac) = self.peek_amount(2).token_type
+            && starts_numeric(&frac) {
+                self.consume();
+                self.consume();
+                val = format!("{val}.{frac}");
+            }
         
+        // the tokeniser splits 1e-5 on the sign
+        if !is_radix
+            && (val.ends_with('e') || val.ends_with('E'))
+            && matches!(self.peek().token_type, TokenType::Plus | TokenType::Minus)
+            && let TokenType::Text(exp) = self.peek_amount(2).token_type
+            && is_numeric(&exp) {
+                val = format!("{val}{}{exp}", self.consume());
+                self.consume();
+            }
+        
+        let Some(content) = parse_num(&val) else {
+            return Err(Error::InvalidNumber {
+                range: Box::new(Range { start, end: self.get_last_end() })
+            });
+        };
         
         let mut is_percentage = false;
         if self.peek() == TokenType::Mod {
@@ -951,14 +1717,46 @@ impl Parser {
         
         Ok(if is_percentage {
             AstExpression::Percentage {
-                content: val.parse().unwrap(),
+                content,
                 range: Range { start, end: self.get_last_end() }
             }
         } else {
             AstExpression::Number {
-                content: val.parse().unwrap(),
+                content,
                 range: Range { start, end: self.get_last_end() }
             }
         })
     }
 }
+
+fn starts_numeric(txt: &str) -> bool {
+    txt.starts_with(|c: char| c.is_ascii_digit())
+}
+fn radix_of(txt: &str) -> Option<u32> {
+    match txt.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0b" | "0B") => Some(2),
+        Some("0o" | "0O") => Some(8),
+        
+        _ => None
+    }
+}
+// reads 1_000, 1.5, 2e10, 0xff, 0b101 and 0o17
+fn parse_num(txt: &str) -> Option<f64> {
+    // separators have to sit between digits
+    if txt.ends_with('_') || txt.contains("_.") || txt.contains("._") {
+        return None;
+    }
+    let txt = txt.replace('_', "");
+    
+    if let Some(radix) = radix_of(&txt) {
+        return u64::from_str_radix(&txt[2..], radix)
+            .ok()
+            .map(|num| num as f64);
+    }
+    
+    if !txt.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-')) {
+        return None;
+    }
+    txt.parse().ok()
+}
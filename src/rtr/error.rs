@@ -1,7 +1,13 @@
 use std::fmt::{Display, Formatter};
+use crate::rtr::runtime::frame::CallFrame;
+use crate::rtr::runtime::memory::MemPointer;
+use crate::shared::diagnostic::Diagnostic;
 use crate::shared::range::Range;
 use crate::shared::token::{Token, TokenType};
 
+// deep recursion would otherwise bury the error under its own trace
+const MAX_TRACE_NOTES: usize = 16;
+
 #[derive(Debug)]
 pub enum Error {
     Placeholder,
@@ -24,13 +30,302 @@ pub enum Error {
         
         range: Range
     },
+    InvalidNumber {
+        range: Box<Range>
+    },
+    InvalidExport {
+        range: Box<Range>
+    },
+    
+    // compiler
+    CannotAssign {
+        to: String,
+        range: Box<Range>
+    },
+    OutsideLoop {
+        keyword: String,
+        range: Box<Range>
+    },
+    UnknownLoopLabel {
+        label: String,
+        range: Box<Range>
+    },
+    MisplacedSpread {
+        range: Box<Range>
+    },
     
     // runtime
     CannotCall {
-        func: String
+        func: String,
+        // filled in by whoever knows where the call happened
+        range: Option<Box<Range>>
     },
-    CannotAssign {
-        to: String
+    // what the builtin takes, like "(ms, function)"
+    InvalidArgs {
+        func: String,
+        wanted: String,
+        range: Option<Box<Range>>
+    },
+    UnknownMethod {
+        method: String,
+        type_name: String,
+        range: Option<Box<Range>>
+    },
+    CannotExtend {
+        type_name: String,
+        range: Option<Box<Range>>
+    },
+    CannotImport {
+        path: String,
+        reason: String,
+        range: Option<Box<Range>>
+    },
+    // an imported file that didnt parse or compile, error points into that file rather than this one
+    InvalidImport {
+        path: String,
+        error: Box<Error>,
+        range: Option<Box<Range>>
+    },
+    // the files in the order they imported each other, starting and ending with the same one
+    ImportCycle {
+        chain: Vec<String>,
+        range: Option<Box<Range>>
+    },
+    CannotWait {
+        range: Option<Box<Range>>
+    },
+    CannotStore {
+        reason: String,
+        range: Option<Box<Range>>
+    },
+    InvalidJson {
+        reason: String,
+        range: Option<Box<Range>>
+    },
+    
+    InstructionLimit {
+        limit: usize,
+        range: Option<Box<Range>>
+    },
+    CallDepthLimit {
+        limit: usize,
+        range: Option<Box<Range>>
+    },
+    HeapLimit {
+        limit: usize,
+        range: Option<Box<Range>>
+    },
+    // a value thrown by the script, with what it stringified to so it can be shown uncaught
+    Thrown {
+        value: MemPointer,
+        message: String,
+        range: Option<Box<Range>>
+    },
+    
+    // a runtime error along with the frames that were running, innermost first
+    Traced {
+        error: Box<Error>,
+        trace: Vec<CallFrame>
+    }
+}
+
+impl Error {
+    pub fn range(&self) -> Option<&Range> {
+        match self {
+            Error::Placeholder =>
+                None,
+            
+            Error::UnexpectedToken { range, .. }
+            | Error::Expected { range, .. }
+            | Error::ExpectedText { range, .. }
+            | Error::InvalidNumber { range }
+            | Error::InvalidExport { range }
+            | Error::CannotAssign { range, .. }
+            | Error::OutsideLoop { range, .. }
+            | Error::UnknownLoopLabel { range, .. }
+            | Error::MisplacedSpread { range } =>
+                Some(range),
+            Error::InvalidColor { range } =>
+                Some(range),
+            
+            Error::CannotCall { range, .. }
+            | Error::InvalidArgs { range, .. }
+            | Error::UnknownMethod { range, .. }
+            | Error::CannotExtend { range, .. }
+            | Error::CannotImport { range, .. }
+            | Error::InvalidImport { range, .. }
+            | Error::ImportCycle { range, .. }
+            | Error::CannotWait { range }
+            | Error::CannotStore { range, .. }
+            | Error::InvalidJson { range, .. }
+            | Error::InstructionLimit { range, .. }
+            | Error::CallDepthLimit { range, .. }
+            | Error::HeapLimit { range, .. }
+            | Error::Thrown { range, .. } =>
+                range.as_deref(),
+            
+            Error::Traced { error, .. } =>
+                error.range()
+        }
+    }
+    
+    // the name scripts see when they catch this
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Placeholder => "Placeholder",
+            
+            Error::UnexpectedToken { .. } => "UnexpectedToken",
+            Error::Expected { .. } => "Expected",
+            Error::ExpectedText { .. } => "ExpectedText",
+            Error::InvalidColor { .. } => "InvalidColor",
+            Error::InvalidNumber { .. } => "InvalidNumber",
+            Error::InvalidExport { .. } => "InvalidExport",
+            
+            Error::CannotAssign { .. } => "CannotAssign",
+            Error::OutsideLoop { .. } => "OutsideLoop",
+            Error::UnknownLoopLabel { .. } => "UnknownLoopLabel",
+            Error::MisplacedSpread { .. } => "MisplacedSpread",
+            
+            Error::CannotCall { .. } => "CannotCall",
+            Error::InvalidArgs { .. } => "InvalidArgs",
+            Error::UnknownMethod { .. } => "UnknownMethod",
+            Error::CannotExtend { .. } => "CannotExtend",
+            Error::CannotImport { .. } => "CannotImport",
+            Error::InvalidImport { .. } => "InvalidImport",
+            Error::ImportCycle { .. } => "ImportCycle",
+            Error::CannotWait { .. } => "CannotWait",
+            Error::CannotStore { .. } => "CannotStore",
+            Error::InvalidJson { .. } => "InvalidJson",
+            Error::InstructionLimit { .. } => "InstructionLimit",
+            Error::CallDepthLimit { .. } => "CallDepthLimit",
+            Error::HeapLimit { .. } => "HeapLimit",
+            Error::Thrown { .. } => "Thrown",
+            
+            Error::Traced { error, .. } => error.kind()
+        }
+    }
+    // limits stop the script for the host, so a try cant be used to get around them
+    pub fn is_catchable(&self) -> bool {
+        match self {
+            Error::InstructionLimit { .. }
+            | Error::CallDepthLimit { .. }
+            | Error::HeapLimit { .. } =>
+                false,
+            Error::Traced { error, .. } =>
+                error.is_catchable(),
+            
+            _ => true
+        }
+    }
+    
+    // fills in the range if the error doesnt know where it happened
+    pub fn or_range(mut self, with: Option<&Range>) -> Error {
+        if let Error::CannotCall { range, .. }
+            | Error::InvalidArgs { range, .. }
+            | Error::UnknownMethod { range, .. }
+            | Error::CannotExtend { range, .. }
+            | Error::CannotImport { range, .. }
+            | Error::InvalidImport { range, .. }
+            | Error::ImportCycle { range, .. }
+            | Error::CannotWait { range }
+            | Error::CannotStore { range, .. }
+            | Error::InvalidJson { range, .. }
+            | Error::InstructionLimit { range, .. }
+            | Error::CallDepthLimit { range, .. }
+            | Error::HeapLimit { range, .. }
+            | Error::Thrown { range, .. } = &mut self
+            && range.is_none() {
+                *range = with.cloned().map(Box::new);
+            }
+        
+        self
+    }
+    
+    // forgets where the error happened, for errors from another file whose ranges dont point into this one
+    pub fn without_range(mut self) -> Error {
+        if let Error::CannotCall { range, .. }
+            | Error::InvalidArgs { range, .. }
+            | Error::UnknownMethod { range, .. }
+            | Error::CannotExtend { range, .. }
+            | Error::CannotImport { range, .. }
+            | Error::InvalidImport { range, .. }
+            | Error::ImportCycle { range, .. }
+            | Error::CannotWait { range }
+            | Error::CannotStore { range, .. }
+            | Error::InvalidJson { range, .. }
+            | Error::InstructionLimit { range, .. }
+            | Error::CallDepthLimit { range, .. }
+            | Error::HeapLimit { range, .. }
+            | Error::Thrown { range, .. } = &mut self {
+            *range = None;
+        }
+        
+        self
+    }
+    
+    pub fn diagnostic(&self) -> Diagnostic {
+        if let Error::Traced { error, trace } = self {
+            let diagnostic = trace
+                .iter()
+                .take(MAX_TRACE_NOTES)
+                .fold(error.diagnostic(), |diagnostic, frame| diagnostic.with_note(frame.to_string()));
+            
+            return if trace.len() > MAX_TRACE_NOTES {
+                diagnostic.with_note(format!("... and {} more", trace.len() - MAX_TRACE_NOTES))
+            } else {
+                diagnostic
+            };
+        }
+        // shown in the file that is broken, with its own notes
+        if let Error::InvalidImport { path, error, .. } = self {
+            return Diagnostic {
+                message: self.to_string(),
+                file: Some(path.clone()),
+                ..error.diagnostic()
+            };
+        }
+        
+        let diagnostic = Diagnostic::new(self.to_string())
+            .with_range(self.range());
+        
+        match self {
+            Error::Expected { wanted, .. } if wanted.contains(&TokenType::SemiColon) =>
+                diagnostic.with_help("statements end with a ';'"),
+            Error::ExpectedText { .. } =>
+                diagnostic.with_note("names can only contain letters, numbers and '_'"),
+            Error::InvalidColor { .. } =>
+                diagnostic.with_help("colors are written as #rgb or #rrggbb"),
+            Error::InvalidNumber { .. } =>
+                diagnostic.with_help("numbers are written like 12, 1.5, 1_000, 2e10, 0xff or 0b101"),
+            Error::InvalidExport { .. } =>
+                diagnostic.with_help("export a declaration, `export name := value;` or `export class Name {}`"),
+            Error::CannotAssign { .. } =>
+                diagnostic.with_note("only variables and properties can be assigned to"),
+            Error::OutsideLoop { .. } =>
+                diagnostic.with_note("break and continue can only be used inside while, until, repeat or for"),
+            Error::UnknownLoopLabel { .. } =>
+                diagnostic.with_help("label a loop with `name: while (...) {}`"),
+            Error::MisplacedSpread { .. } =>
+                diagnostic.with_note("values can only be spread into arrays, objects and call args"),
+            Error::CannotCall { .. } =>
+                diagnostic.with_note("only functions can be called"),
+            Error::UnknownMethod { .. } =>
+                diagnostic.with_note("methods come from the value's own keys or from its type"),
+            Error::CannotImport { .. } =>
+                diagnostic.with_note("imports are found relative to the file doing the importing"),
+            Error::ImportCycle { .. } =>
+                diagnostic.with_help("move what both files need into a third one they can both import"),
+            Error::CannotWait { .. } =>
+                diagnostic.with_note("callbacks given to builtins, module bodies and events called from a script run to completion"),
+            Error::InstructionLimit { .. } =>
+                diagnostic.with_note("the script was stopped in case it never finishes"),
+            Error::CallDepthLimit { .. } =>
+                diagnostic.with_help("check for a function or event that keeps calling itself"),
+            Error::HeapLimit { .. } =>
+                diagnostic.with_note("the script was stopped for holding onto too many values"),
+            
+            _ => diagnostic
+        }
     }
 }
 
@@ -43,8 +338,8 @@ impl Display for Error {
             // ast
             Error::UnexpectedToken { token, .. } =>
                 write!(f, "unexpected token '{token}'"),
-            Error::Expected { wanted, got, range } =>
-                write!(f, "expected {}, got '{got}' at {range}",
+            Error::Expected { wanted, got, .. } =>
+                write!(f, "expected {}, got '{got}'",
                        wanted
                            .iter()
                            .map(|t| format!("'{t}'"))
@@ -55,12 +350,52 @@ impl Display for Error {
                 write!(f, "expected text, got {got}"),
             Error::InvalidColor { .. } =>
                 write!(f, "invalid hex color, must follow #rgb or #rrggbb"),
+            Error::InvalidNumber { .. } =>
+                write!(f, "invalid number literal"),
+            Error::InvalidExport { .. } =>
+                write!(f, "only declarations can be exported"),
+            
+            // compiler
+            Error::CannotAssign { to, .. } =>
+                write!(f, "cannot assign to {to}"),
+            Error::OutsideLoop { keyword, .. } =>
+                write!(f, "'{keyword}' outside of a loop"),
+            Error::UnknownLoopLabel { label, .. } =>
+                write!(f, "no enclosing loop labelled '{label}'"),
+            Error::MisplacedSpread { .. } =>
+                write!(f, "cannot spread here"),
             
             // runtime
-            Error::CannotCall { func } =>
+            Error::CannotCall { func, .. } =>
                 write!(f, "cannot call '{func}' as func"),
-            Error::CannotAssign { to } =>
-                write!(f, "cannot assign to {to}"),
+            Error::InvalidArgs { func, wanted, .. } =>
+                write!(f, "{func} takes {wanted}"),
+            Error::UnknownMethod { method, type_name, .. } =>
+                write!(f, "{type_name} has no method '{method}'"),
+            Error::CannotExtend { type_name, .. } =>
+                write!(f, "cannot extend {type_name}, only classes"),
+            Error::CannotImport { path, reason, .. } =>
+                write!(f, "cannot import '{path}': {reason}"),
+            Error::InvalidImport { path, error, .. } =>
+                write!(f, "cannot import '{path}': {error}"),
+            Error::ImportCycle { chain, .. } =>
+                write!(f, "import cycle: {}", chain.join(" -> ")),
+            Error::CannotWait { .. } =>
+                write!(f, "can only wait in events ran by the host and the functions they call"),
+            Error::CannotStore { reason, .. } =>
+                write!(f, "storage failed: {reason}"),
+            Error::InvalidJson { reason, .. } =>
+                write!(f, "invalid json: {reason}"),
+            Error::InstructionLimit { limit, .. } =>
+                write!(f, "ran more than {limit} instructions"),
+            Error::CallDepthLimit { limit, .. } =>
+                write!(f, "calls nested deeper than {limit}"),
+            Error::HeapLimit { limit, .. } =>
+                write!(f, "heap grew past {limit} values"),
+            Error::Thrown { message, .. } =>
+                write!(f, "uncaught {message}"),
+            Error::Traced { error, .. } =>
+                write!(f, "{error}"),
         }
     }
 }
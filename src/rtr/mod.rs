@@ -1,41 +1,101 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
 use crate::{print_raw, print_warn, Log, LogKind, print_log};
 use crate::rtr::apis::inject;
 use crate::rtr::ast::node::{AssignmentOp, AstProgram, AstStatement, AstTopLevelStatement, BinaryOp, EventTarget, UnaryOp};
 use crate::rtr::ast::parser::Parser;
-use crate::rtr::ast::tokenise;
+use crate::rtr::ast::{tokenise, tokenise_at};
 use crate::rtr::error::Error;
 use crate::rtr::log::RTRLog;
+use crate::rtr::modules::ModuleCache;
 use crate::rtr::runtime::compiler;
 use crate::rtr::runtime::compiler::CompileContext;
-use crate::rtr::runtime::instruction::VmInstruction;
-use crate::rtr::runtime::memory::{MemPointer, Memory};
+use crate::rtr::runtime::frame::{CallFrame, FrameKind};
+use crate::rtr::runtime::instruction::{ArgCount, Chunk, Var, VmInstruction};
+use crate::rtr::runtime::limits::Limits;
+use crate::rtr::runtime::memory::{HeapStats, MemPointer, Memory};
+use crate::rtr::runtime::prototype::{Prototype, Prototypes};
+use crate::rtr::runtime::prototype;
+use crate::rtr::runtime::remote::{self, Handle};
 use crate::rtr::runtime::scope::Scope;
-use crate::rtr::runtime::value::{BuiltinFunction, Function, Value};
+use crate::rtr::runtime::shared::{Broadcast, SharedValue};
+use crate::rtr::runtime::storage::Storage;
+use crate::rtr::runtime::symbol::Symbol;
+use crate::rtr::runtime::timers::Timers;
+use crate::rtr::runtime::value::{class_method, BuiltinFunction, Function, Value};
+use crate::shared::diagnostic::Diagnostic;
 use crate::shared::logging::LogSource;
+use crate::shared::position::Position;
+use crate::shared::range::Range;
+use crate::shared::token::Token;
 
 pub mod ast;
 pub mod runtime;
 pub(crate) mod error;
 pub mod log;
+pub mod modules;
 mod apis;
 
-#[derive(Debug)]
-struct Event {
-    pub body: Vec<AstStatement>,
-    pub target: EventTarget
-}
-
 #[derive(Debug)]
 pub struct RTRModule {
     pub ast: Option<AstProgram>,
+    // where the source came from, used when reporting errors
+    pub path: Option<PathBuf>,
+    // every event compiled once at parse time, in the order they were written
+    events: HashMap<EventTarget, Vec<EventHandler>>,
+    // the code outside of events, taken out when it runs so it only runs once
+    body: Option<Rc<Chunk>>,
+    
+    // parsed imports, shared with the other scripts of a document
+    pub modules: Rc<RefCell<ModuleCache>>,
+    // what each module this script imported exports, copied in from the cache
+    imported: HashMap<PathBuf, MemPointer>,
     
     // runtime
     pub stack: Vec<MemPointer>,
     pub scope: Scope,
     pub memory: Memory,
+    pub frames: Vec<CallFrame>,
+    pub prototypes: Prototypes,
+    pub limits: Limits,
+    pub timers: Timers,
+    // what the storage object reads and writes, shared with the other scripts of a document
+    pub storage: Rc<RefCell<Storage>>,
+    // instructions ran since the host last called in, counted against limits.instructions
+    instructions_ran: usize,
+    
+    // the module that called into this one, put aside while this one runs in its place
+    caller: Option<Box<RTRModule>>,
+    // frames the callers have open, counted against limits.call_depth along with this one's
+    outer_frames: usize,
+    // values other modules were given handles to, kept alive while the handles are
+    handles: Vec<Weak<Handle>>,
+    
+    // events put aside by wait, carried on with by resume
+    routines: Vec<Routine>,
+    // set by suspend, the running event is put aside once the instruction that set it is done
+    suspending: Option<Wake>,
+    // whether what is running right now could be put aside
+    suspendable: bool,
+    // what the host gave for each signal, until the event waiting on it carries on
+    signals: HashMap<usize, SharedValue>,
+    next_signal: usize,
     
-    pub logs: Vec<RTRLog>
+    // literals from the source, referenced by index from the bytecode
+    pub constants: Vec<Value>,
+    
+    pub logs: Vec<RTRLog>,
+    // sent by broadcast, waiting for the host to hand them to every script
+    pub broadcasts: Vec<Broadcast>
+}
+
+// a compiled event, along with the names its args are bound to
+#[derive(Debug, Clone)]
+struct EventHandler {
+    chunk: Rc<Chunk>,
+    params: Vec<Symbol>
 }
 
 #[derive(Debug)]
@@ -45,14 +105,42 @@ pub struct RTRInstance {
 
 impl RTRModule {
     pub fn new() -> RTRModule {
+        let mut memory = Memory::default();
+        let scope = Scope::new(&mut memory);
+        
         let mut module = RTRModule {
             ast: None,
+            path: None,
+            events: HashMap::new(),
+            body: None,
+            
+            modules: Rc::default(),
+            imported: HashMap::new(),
             
             stack: Vec::new(),
-            scope: Scope::new(),
-            memory: Memory::default(),
+            scope,
+            memory,
+            frames: Vec::new(),
+            prototypes: Prototypes::default(),
+            limits: Limits::default(),
+            timers: Timers::default(),
+            storage: Rc::default(),
+            instructions_ran: 0,
+            
+            caller: None,
+            outer_frames: 0,
+            handles: Vec::new(),
             
-            logs: Vec::new()
+            routines: Vec::new(),
+            suspending: None,
+            suspendable: false,
+            signals: HashMap::new(),
+            next_signal: 0,
+            
+            constants: Vec::new(),
+            
+            logs: Vec::new(),
+            broadcasts: Vec::new()
         };
         
         module.init();
@@ -62,6 +150,12 @@ impl RTRModule {
     
     pub fn init(&mut self) {
         self.new_scope();
+        self.prototypes = prototype::builtins(&mut self.memory);
+    }
+    
+    // lets scripts call func as `value.name(args)` on every value of that type, with the value as the first arg
+    pub fn add_method(&mut self, proto: Prototype, name: &str, func: Function) {
+        self.prototypes.add(&mut self.memory, proto, name, func);
     }
     
     // add apis
@@ -71,12 +165,14 @@ impl RTRModule {
     
     fn set_alloc(&mut self, name: &str, value: Value) {
         let ptr = self.memory.alloc(value);
+        let name = self.scope.symbols.intern(name);
         self.scope.set_var(
             &mut self.memory,
-            String::from(name),
+            name,
             ptr
         );
     }
+    #[allow(clippy::too_many_lines)]
     pub fn new_scope(&mut self) {
         self.set_alloc("true", Value::Bool { data: true });
         self.set_alloc("false", Value::Bool { data: false });
@@ -88,15 +184,29 @@ impl RTRModule {
         self.set_alloc("error", Value::Function(
             Function::Builtin(BuiltinFunction::Error)
         ));
-        self.set_alloc("return", Value::Function(
-            Function::Builtin(BuiltinFunction::Return)
-        ));
         self.set_alloc("typeof", Value::Function(
             Function::Builtin(BuiltinFunction::Typeof)
         ));
         self.set_alloc("length", Value::Function(
             Function::Builtin(BuiltinFunction::Length)
         ));
+        self.set_alloc("broadcast", Value::Function(
+            Function::Builtin(BuiltinFunction::Broadcast)
+        ));
+        
+        // time
+        self.set_alloc("after", Value::Function(
+            Function::Builtin(BuiltinFunction::After)
+        ));
+        self.set_alloc("cancel", Value::Function(
+            Function::Builtin(BuiltinFunction::Cancel)
+        ));
+        self.set_alloc("now", Value::Function(
+            Function::Builtin(BuiltinFunction::Now)
+        ));
+        self.set_alloc("wait", Value::Function(
+            Function::Builtin(BuiltinFunction::Wait)
+        ));
         
         // mathematical
         self.set_alloc("min", Value::Function(
@@ -148,6 +258,14 @@ impl RTRModule {
             Function::Builtin(BuiltinFunction::ToTitle)
         ));
         
+        // json
+        self.set_alloc("jsonParse", Value::Function(
+            Function::Builtin(BuiltinFunction::JsonParse)
+        ));
+        self.set_alloc("jsonStringify", Value::Function(
+            Function::Builtin(BuiltinFunction::JsonStringify)
+        ));
+        
         // array
         self.set_alloc("item", Value::Function(
             Function::Builtin(BuiltinFunction::Item)
@@ -180,82 +298,338 @@ impl RTRModule {
         self.set_alloc("not", Value::Function(
             Function::Builtin(BuiltinFunction::Not)
         ));
+        
+        // callbacks
+        self.set_alloc("map", Value::Function(
+            Function::Builtin(BuiltinFunction::Map)
+        ));
+        self.set_alloc("filter", Value::Function(
+            Function::Builtin(BuiltinFunction::Filter)
+        ));
+        self.set_alloc("reduce", Value::Function(
+            Function::Builtin(BuiltinFunction::Reduce)
+        ));
+        self.set_alloc("sort", Value::Function(
+            Function::Builtin(BuiltinFunction::Sort)
+        ));
+        self.set_alloc("find", Value::Function(
+            Function::Builtin(BuiltinFunction::Find)
+        ));
+        self.set_alloc("findIndex", Value::Function(
+            Function::Builtin(BuiltinFunction::FindIndex)
+        ));
+        self.set_alloc("forEach", Value::Function(
+            Function::Builtin(BuiltinFunction::ForEach)
+        ));
+        self.set_alloc("some", Value::Function(
+            Function::Builtin(BuiltinFunction::Some)
+        ));
+        self.set_alloc("every", Value::Function(
+            Function::Builtin(BuiltinFunction::Every)
+        ));
     }
     
     pub fn parse(&mut self, src: &str) -> Result<(), Error> {
-        let tokens = tokenise(src);
+        self.parse_tokens(tokenise(src))
+    }
+    // parses a source embedded in a bigger one, so ranges point into the outer source
+    pub fn parse_at(&mut self, src: &str, start: Position) -> Result<(), Error> {
+        self.parse_tokens(tokenise_at(src, start))
+    }
+    fn parse_tokens(&mut self, tokens: Vec<Token>) -> Result<(), Error> {
         let mut parser = Parser {
             pointer: 0,
             tokens
         };
         
-        let out = parser.parse();
-        match out {
-            Err(err) => {
-                print_warn!(LogSource::Rtr, "{err}");
+        let ast = parser.parse()?;
+        let path = self.path.clone();
+        self.events = self.compile_events(&ast)?;
+        self.body = Some(Rc::new(self.compile_body(&ast, path.as_deref())?));
+        self.ast = Some(ast);
+        
+        Ok(())
+    }
+    fn compile_events(&mut self, ast: &AstProgram) -> Result<HashMap<EventTarget, Vec<EventHandler>>, Error> {
+        let mut events: HashMap<EventTarget, Vec<EventHandler>> = HashMap::new();
+        
+        for event in &ast.events {
+            let AstTopLevelStatement::Event { body, target, params } = event else {
+                continue;
+            };
+            
+            let handler = EventHandler {
+                chunk: Rc::new(self.compile_event(body.clone())?),
+                params: params
+                    .iter()
+                    .map(|param| self.scope.symbols.intern(param))
+                    .collect()
+            };
+            events
+                .entry(target.clone())
+                .or_default()
+                .push(handler);
+        }
+        
+        Ok(events)
+    }
+    
+    pub fn diagnostic(&self, err: &Error) -> Diagnostic {
+        err.diagnostic()
+            .or_file(self.path.as_ref().map(|path| path.display()))
+    }
+    
+    // gives back what the last handler to return something returned, like false to cancel what caused the event
+    pub fn run_event_target(&mut self, target: &EventTarget) -> Result<Option<Value>, Error> {
+        self.run_event_target_with(target, self.limits.clone())
+    }
+    // runs with different limits just for this call
+    pub fn run_event_target_with(&mut self, target: &EventTarget, limits: Limits) -> Result<Option<Value>, Error> {
+        self.run_from_host(target, limits, &[])
+    }
+    // runs with args from outside of this module, like a broadcast from another script or the frame time
+    pub fn run_event_target_args(&mut self, target: &EventTarget, args: &[SharedValue]) -> Result<Option<Value>, Error> {
+        self.run_from_host(target, self.limits.clone(), args)
+    }
+    fn run_from_host(&mut self, target: &EventTarget, limits: Limits, args: &[SharedValue]) -> Result<Option<Value>, Error> {
+        let limits = std::mem::replace(&mut self.limits, limits);
+        self.instructions_ran = 0;
+        
+        let args: Vec<MemPointer> = args
+            .iter()
+            .map(|arg| arg.copy_in(&mut self.memory))
+            .collect();
+        for arg in &args {
+            self.memory.pin(*arg);
+        }
+        
+        let out = self.run_body()
+            .and_then(|()| self.run_event_target_at(target, None, &args));
+        self.limits = limits;
+        
+        for arg in &args {
+            self.memory.unpin(*arg);
+        }
+        
+        Ok(out?.map(|ptr| self.memory.get(ptr).clone()))
+    }
+    // calls every timer that is due, the host does this each frame
+    pub fn run_timers(&mut self) -> Result<(), Error> {
+        self.instructions_ran = 0;
+        
+        let now = self.timers.now();
+        for id in self.timers.due(now) {
+            // an earlier timer may have cancelled it
+            let Some(func) = self.timers.fire(id, now) else {
+                continue;
+            };
+            
+            // a timer that only runs once isnt held onto by the timers anymore
+            self.memory.pin(func);
+            let out = self.begin_call(func, &[], None)
+                .and_then(|callee| match callee {
+                    Callee::Done(_) => Ok(None),
+                    Callee::Vm(activation) => self.run_entered(activation, true)
+                });
+            self.memory.unpin(func);
+            out?;
+        }
+        
+        Ok(())
+    }
+    
+    // puts the running event aside once the current call returns, for builtins like wait
+    pub fn suspend(&mut self, wake: Wake) -> Result<(), Error> {
+        if !self.suspendable {
+            return Err(Error::CannotWait { range: None });
+        }
+        
+        self.suspending = Some(wake);
+        Ok(())
+    }
+    // an id for the host to give a value to later with signal, for events to wait on
+    pub fn new_signal(&mut self) -> usize {
+        self.next_signal += 1;
+        self.next_signal
+    }
+    pub fn signal(&mut self, id: usize, value: SharedValue) {
+        self.signals.insert(id, value);
+    }
+    // carries on with the events that are done waiting, the host does this each frame
+    pub fn resume(&mut self) -> Result<(), Error> {
+        self.instructions_ran = 0;
+        
+        let now = self.timers.now();
+        let (ready, waiting): (Vec<Routine>, Vec<Routine>) = std::mem::take(&mut self.routines)
+            .into_iter()
+            .partition(|routine| match routine.wake {
+                Wake::At(at) => at <= now,
+                Wake::Signal(id) => self.signals.contains_key(&id)
+            });
+        self.routines = waiting;
+        
+        let mut ready = ready.into_iter();
+        while let Some(routine) = ready.next() {
+            if let Err(err) = self.resume_routine(routine) {
+                // the rest get another go next time
+                self.routines.extend(ready);
                 return Err(err);
             }
-            Ok(ast) => {
-                self.ast = Some(ast);
-            }
         }
         
         Ok(())
     }
-    
-    // TODO: find a better way to do this? :sob:
-    fn get_eligible(&mut self, target: &EventTarget) -> Vec<Event> {
-        if self.ast.is_none() {
-            return Vec::new()
+    fn resume_routine(&mut self, routine: Routine) -> Result<(), Error> {
+        let value = if let Wake::Signal(id) = routine.wake {
+            self.signals.remove(&id)
+        } else {
+            None
+        };
+        
+        // the routine has the vm to itself until it finishes or waits again
+        let stack = std::mem::replace(&mut self.stack, routine.stack);
+        let callers = std::mem::replace(&mut self.scope.callers, routine.callers);
+        let env = std::mem::replace(&mut self.scope.env, routine.env);
+        let frames = std::mem::replace(&mut self.frames, routine.frames);
+        
+        // the call that waited returns what the host gave
+        if let Some(value) = value {
+            let ptr = value.copy_in(&mut self.memory);
+            *self.stack.last_mut().expect("a call waited so its result is on the stack") = ptr;
         }
         
-        let ast_events = &self.ast.as_ref().unwrap().events;
-        let mut events = Vec::new();
-        
-        for event in ast_events {
-            #[allow(irrefutable_let_patterns)]
-            if let AstTopLevelStatement::Event {
-                body,
-                target: ev_target
-            } = event
-                && match (ev_target, target.clone()) {
-                    (EventTarget::Global { name: ev_name }, EventTarget::Global { name }) =>
-                        *ev_name == name,
-                    _ => false
-                } {
-                    events.push(Event {
-                        body: body.clone(),
-                        target: ev_target.clone()
-                    });
-                }
+        let out = self.execute(routine.activations, true);
+        
+        self.stack = stack;
+        self.scope.callers = callers;
+        self.scope.env = env;
+        self.frames = frames;
+        
+        out.map(|_| ())
+    }
+    // the broadcasts sent since this was last called
+    pub fn take_broadcasts(&mut self) -> Vec<Broadcast> {
+        std::mem::take(&mut self.broadcasts)
+    }
+    // runs the code outside of events now rather than before the first event,
+    // for hosts that want a broken import to fail loading
+    pub fn load(&mut self) -> Result<(), Error> {
+        self.instructions_ran = 0;
+        self.run_body()
+    }
+    // runs the code outside of events, the first time the host calls in
+    fn run_body(&mut self) -> Result<(), Error> {
+        let Some(body) = self.body.take() else {
+            return Ok(());
+        };
+        
+        let name = self.path.as_ref().map(|path| path.display().to_string());
+        self.run_module(body, self.scope.global, name, None)?;
+        Ok(())
+    }
+    // runs a file's top level in env, giving back the object of what it exports
+    fn run_module(&mut self, chunk: Rc<Chunk>, env: MemPointer, name: Option<String>, call_site: Option<&Range>) -> Result<MemPointer, Error> {
+        self.push_frame(CallFrame {
+            kind: FrameKind::Module,
+            name,
+            call_site: call_site.cloned()
+        })?;
+        self.scope.enter(env);
+        let out = self.run_entered(self.activation(chunk), false);
+        
+        Ok(out?.expect("module bodies return their exports"))
+    }
+    // what a module exports, running it the first time anything in the document imports it
+    fn import(&mut self, path: &Path, call_site: Option<&Range>) -> Result<MemPointer, Error> {
+        if let Some(exports) = self.imported.get(path) {
+            return Ok(*exports);
+        }
+        
+        let cached = self.modules.borrow().exports(path);
+        let exports = match cached {
+            Some(exports) => exports,
+            None => self.evaluate(path, call_site)?
+        };
+        
+        let exports = exports.copy_in(&mut self.memory);
+        self.imported.insert(path.to_path_buf(), exports);
+        Ok(exports)
+    }
+    fn evaluate(&mut self, path: &Path, call_site: Option<&Range>) -> Result<SharedValue, Error> {
+        let name = path.display().to_string();
+        if let Err(chain) = self.modules.borrow_mut().enter(path) {
+            return Err(self.trace_error(Error::ImportCycle { chain, range: None }, call_site));
         }
         
-        events
+        // modules get a module of their own, so every script sees the same one.
+        // it is kept in the cache rather than holding onto it, and uses the caller's whenever it runs
+        let mut module = RTRModule::new();
+        module.path = Some(path.to_path_buf());
+        module.storage = Rc::clone(&self.storage);
+        module.inject();
+        
+        let ast = self.modules.borrow_mut().load(path);
+        let chunk = ast.and_then(|ast| module.compile_body(&ast, Some(path)).map_err(|err| Error::InvalidImport {
+            path: name.clone(),
+            error: Box::new(err),
+            range: None
+        }));
+        let out = chunk.and_then(|chunk| remote::evaluate(self, Box::new(module), Rc::new(chunk), name));
+        
+        let mut modules = self.modules.borrow_mut();
+        modules.leave();
+        let (exports, module) = out.map_err(|err| self.trace_error(err, call_site))?;
+        modules.evaluated(path, exports.clone(), module);
+        Ok(exports)
     }
     
-    pub fn run_event_target(&mut self, target: &EventTarget) -> Result<Option<Value>, Error> {
-        // TODO: cache compiled segments :P
-        let events = self.get_eligible(target);
+    // call_site is where the event was called from in the script, if it was
+    fn run_event_target_at(&mut self, target: &EventTarget, call_site: Option<&Range>, args: &[MemPointer]) -> Result<Option<MemPointer>, Error> {
+        // cloning the list is cheap and lets events be called while running
+        let handlers = self.events
+            .get(target)
+            .cloned()
+            .unwrap_or_default();
         
         let mut out = None;
         
-        for event in events {
-            let ev_out = self.run_event(event)?;
-            if let Some(data) = ev_out {
-                out = Some(data);
+        for handler in handlers {
+            let ev_out = self.run_event(target, &handler, call_site.cloned(), args);
+            if let Some(ptr) = out {
+                self.memory.unpin(ptr);
+            }
+            
+            // keep the output alive while the rest of the events run
+            if let Some(ptr) = ev_out? {
+                self.memory.pin(ptr);
+                out = Some(ptr);
             }
         }
         
-        Ok(out)
+        Ok(out.inspect(|ptr| self.memory.unpin(*ptr)))
     }
-    fn run_event(&mut self, event: Event) -> Result<Option<Value>, Error> {
-        let instructions = self.compile_event(event.body)?;
-        //println!("{instructions:?}");
-        let val = self.run_instructions(&instructions)?;
+    fn run_event(&mut self, target: &EventTarget, handler: &EventHandler, call_site: Option<Range>, args: &[MemPointer]) -> Result<Option<MemPointer>, Error> {
+        //println!("{chunk:?}");
+        
+        // only events ran by the host can wait, ones called from a script finish before it carries on
+        let suspendable = self.frames.is_empty();
         
-        let val = val.map(|ptr| self.memory.get(ptr).clone());
+        self.push_frame(CallFrame {
+            kind: FrameKind::Event,
+            name: Some(match target {
+                EventTarget::Global { name } => name.clone(),
+                EventTarget::Property { event_name, .. } => event_name.clone()
+            }),
+            call_site
+        })?;
+        // events share the global scope no matter where they were called from
+        self.scope.enter(self.scope.global);
+        for (i, param) in handler.params.iter().enumerate() {
+            let arg = args.get(i).copied().unwrap_or_else(|| self.memory.alloc(Value::Null));
+            self.scope.decl_var(&mut self.memory, *param, arg);
+        }
         
-        Ok(val)
+        self.run_entered(self.activation(Rc::clone(&handler.chunk)), suspendable)
     }
     
     fn pop_stack(&mut self) -> (&Value, MemPointer) {
@@ -281,27 +655,258 @@ impl RTRModule {
         self.stack.push(val);
     }
     
-    #[allow(clippy::too_many_lines)]
-    pub fn run_instructions(&mut self, instructions: &[VmInstruction]) -> Result<Option<MemPointer>, Error> {
-        let mut labels: HashMap<String, usize> = instructions
+    // frees everything that cant be reached from the stack or the scope
+    pub fn collect_garbage(&mut self) {
+        self.memory.collect(self.stack
             .iter()
-            .enumerate()
-            .filter_map(|(i, instruction)| {
-                if let VmInstruction::Label(name) = &instruction {
-                    Some((name.clone(), i))
+            .copied()
+            .chain(self.scope.roots())
+            .chain(self.prototypes.roots())
+            .chain(self.timers.roots())
+            .chain(self.routines.iter().flat_map(Routine::roots))
+            .chain(self.imported.values().copied())
+            .chain(remote::roots(&mut self.handles)));
+    }
+    pub fn heap_stats(&self) -> HeapStats {
+        self.memory.stats()
+    }
+    
+    fn push_frame(&mut self, frame: CallFrame) -> Result<(), Error> {
+        if let Some(limit) = self.limits.call_depth
+            && self.outer_frames + self.frames.len() >= limit {
+                let range = frame.call_site.clone();
+                return Err(self.trace_error(Error::CallDepthLimit { limit, range: None }, range.as_ref()));
+            }
+        
+        self.frames.push(frame);
+        Ok(())
+    }
+    
+    // calls any function value straight away, used by builtins taking callbacks
+    pub fn call_function(&mut self, func_ptr: MemPointer, args: &[MemPointer], call_site: Option<&Range>) -> Result<MemPointer, Error> {
+        match self.begin_call(func_ptr, args, call_site)? {
+            Callee::Done(out) => Ok(out),
+            Callee::Vm(activation) => {
+                let out = self.run_entered(activation, false);
+                Ok(out?.unwrap_or_else(|| self.memory.alloc(Value::Null)))
+            }
+        }
+    }
+    // calls made by the call instructions, functions written in rtr are left for the vm loop to run
+    fn call_step(&mut self, func_ptr: MemPointer, args: &[MemPointer], call_site: Option<&Range>, base: usize) -> Result<Step, Error> {
+        match self.begin_call(func_ptr, args, call_site)? {
+            Callee::Done(out) => {
+                self.stack.truncate(base);
+                self.push_stack_ptr(out);
+                Ok(Step::Next)
+            }
+            Callee::Vm(mut activation) => {
+                activation.base = Some(base);
+                Ok(Step::Call(activation))
+            }
+        }
+    }
+    // runs anything that isnt written in rtr, for the rest it sets up the frame and scope for its body to run in
+    fn begin_call(&mut self, func_ptr: MemPointer, args: &[MemPointer], call_site: Option<&Range>) -> Result<Callee, Error> {
+        let func = self.memory.get(func_ptr).clone();
+        
+        if let Value::Class { .. } = func {
+            return self.construct(func_ptr, args, call_site).map(Callee::Done);
+        }
+        let Value::Function(Function::Vm { body, names, params, name, env }) = &func else {
+            return func.call(self, args)
+                .map(Callee::Done)
+                .map_err(|err| self.trace_error(err, call_site));
+        };
+        
+        self.push_frame(CallFrame {
+            kind: FrameKind::Function,
+            name: name.clone(),
+            call_site: call_site.cloned()
+        })?;
+        
+        // the body runs in the scope the function was defined in, not the caller's
+        self.scope.enter(*env);
+        self.scope.new_layer(&mut self.memory, names);
+        for index in 0..*params {
+            let arg = args.get(index).copied().unwrap_or_else(|| self.memory.alloc(Value::Null));
+            self.scope.decl(&mut self.memory, Var::Slot { depth: 0, index, name: names[index] }, arg);
+        }
+        
+        Ok(Callee::Vm(self.activation(Rc::clone(body))))
+    }
+    
+    // where a call's args start on the stack, and what they are.
+    // spread args come as one array, which stays on the stack so its items cant be collected mid call
+    fn call_args(&self, count: &ArgCount) -> (usize, Vec<MemPointer>) {
+        match count {
+            ArgCount::Fixed(len) => {
+                let start = self.stack.len() - len;
+                (start, self.stack[start..].to_vec())
+            }
+            ArgCount::Spread => {
+                let start = self.stack.len() - 1;
+                let args = if let Value::Arr { items } = self.memory.get(self.stack[start]) {
+                    items.clone()
                 } else {
-                    None
+                    Vec::new()
+                };
+                (start, args)
+            }
+        }
+    }
+    
+    // where `value.name()` finds name, and whether it wants the value passed to it.
+    // functions on an object's own keys are called as they are, then its class is tried, then its type's prototype
+    fn find_method(&self, receiver: &Value, name: &str) -> Option<(MemPointer, bool)> {
+        if let Value::Obj { data, class } = receiver {
+            if let Some(func_ptr) = data.get(name) {
+                return Some((*func_ptr, false));
+            }
+            if let Some(class) = class
+                && let Some(func_ptr) = class_method(&self.memory, *class, name) {
+                    return Some((func_ptr, true));
                 }
-            })
-            .collect();
+        }
+        
+        let proto = Prototype::of(receiver)?;
+        self.prototypes
+            .get(proto, name)
+            .map(|func_ptr| (func_ptr, true))
+    }
+    // makes an instance of a class and runs its init, if it or a parent has one
+    fn construct(&mut self, class: MemPointer, args: &[MemPointer], call_site: Option<&Range>) -> Result<MemPointer, Error> {
+        let instance = self.memory.alloc(Value::Obj {
+            data: HashMap::new(),
+            class: Some(class)
+        });
+        
+        if let Some(init) = class_method(&self.memory, class, "init") {
+            let mut init_args = vec![instance];
+            init_args.extend_from_slice(args);
+            
+            // keep the instance alive while init runs
+            self.stack.push(instance);
+            let out = self.call_function(init, &init_args, call_site);
+            self.stack.pop();
+            out?;
+        }
+        
+        Ok(instance)
+    }
+    
+    // what a catch gets, thrown values as they are and errors as {message, kind, range}
+    fn error_value(&mut self, err: Error) -> MemPointer {
+        let err = if let Error::Traced { error, .. } = err { *error } else { err };
+        if let Error::Thrown { value, .. } = err {
+            return value;
+        }
+        
+        let mut data = HashMap::new();
+        data.insert(String::from("message"), self.memory.alloc(Value::Str { data: err.to_string() }));
+        data.insert(String::from("kind"), self.memory.alloc(Value::Str { data: String::from(err.kind()) }));
+        
+        let range = if let Some(range) = err.range() {
+            let mut range_data = HashMap::new();
+            for (key, pos) in [("start", &range.start), ("end", &range.end)] {
+                let pos = HashMap::from([
+                    (String::from("ln"), self.memory.alloc(Value::Num { data: pos.ln as f64 })),
+                    (String::from("col"), self.memory.alloc(Value::Num { data: pos.col as f64 }))
+                ]);
+                range_data.insert(String::from(key), self.memory.alloc(Value::Obj { data: pos, class: None }));
+            }
+            Value::Obj { data: range_data, class: None }
+        } else {
+            Value::Null
+        };
+        data.insert(String::from("range"), self.memory.alloc(range));
+        
+        self.memory.alloc(Value::Obj { data, class: None })
+    }
+    
+    // attaches where the error happened and the frames that led to it,
+    // errors that already have a trace came from a deeper call and are left alone
+    fn trace_error(&self, err: Error, range: Option<&Range>) -> Error {
+        if let Error::Traced { .. } = err {
+            return err;
+        }
+        
+        Error::Traced {
+            error: Box::new(err.or_range(range)),
+            trace: self.frames.iter().rev().cloned().collect()
+        }
+    }
+    
+    fn activation(&self, chunk: Rc<Chunk>) -> Activation {
+        Activation {
+            chunk,
+            ip: 0,
+            handlers: Vec::new(),
+            start_env: self.scope.env,
+            start_stack: self.stack.len(),
+            base: None
+        }
+    }
+    // runs a body whose frame and scope the caller set up, leaving them once it is done.
+    // a body that got put aside took them with it, and gives back nothing for now
+    fn run_entered(&mut self, activation: Activation, suspendable: bool) -> Result<Option<MemPointer>, Error> {
+        let out = self.execute(vec![activation], suspendable);
+        if let Ok(Exit::Suspended) = out {
+            return Ok(None);
+        }
         
-        let start_scope_layers = self.scope.layers.iter().len();
+        self.scope.leave();
+        self.frames.pop();
+        
+        out.map(|exit| match exit {
+            Exit::Returned(out) => out,
+            Exit::Suspended => None
+        })
+    }
+    
+    // runs until the bottom activation is done, with calls between rtr functions pushing onto
+    // activations instead of recursing, so if suspendable the whole lot can be put aside by a wait
+    fn execute(&mut self, activations: Vec<Activation>, suspendable: bool) -> Result<Exit, Error> {
+        let outer = std::mem::replace(&mut self.suspendable, suspendable);
+        let out = self.execute_activations(activations);
+        self.suspendable = outer;
         
-        let mut i = 0;
-        let mut instructions_ran = 0;
-        while i < instructions.len() && instructions_ran < 10000 {
-            instructions_ran += 1;
-            let inst = &instructions[i];
+        out
+    }
+    fn execute_activations(&mut self, mut activations: Vec<Activation>) -> Result<Exit, Error> {
+        loop {
+            let activation = activations.last_mut().expect("execute always has an activation");
+            let chunk = Rc::clone(&activation.chunk);
+            let i = activation.ip;
+            
+            // falling off the end returns nothing
+            if i >= chunk.instructions.len() {
+                let done = activations.pop().unwrap();
+                if let Some(exit) = self.finish_activation(&done, None) {
+                    return Ok(exit);
+                }
+                continue;
+            }
+            
+            let inst = &chunk.instructions[i];
+            
+            self.instructions_ran += 1;
+            if let Some(limit) = self.limits.instructions
+                && self.instructions_ran > limit {
+                    return Err(self.trace_error(Error::InstructionLimit { limit, range: None }, chunk.span(i)));
+                }
+            
+            // nothing is held outside of the stack and scope between instructions
+            if self.memory.should_collect() {
+                self.collect_garbage();
+            }
+            if let Some(limit) = self.limits.heap_cells
+                && self.memory.live() > limit {
+                    self.collect_garbage();
+                    if self.memory.live() > limit {
+                        return Err(self.trace_error(Error::HeapLimit { limit, range: None }, chunk.span(i)));
+                    }
+                }
             
             if false {
                 println!("{:?}\n    {:?}\n    {:?}", inst, self.stack.iter().map(|ptr| {
@@ -314,349 +919,487 @@ impl RTRModule {
                 }).collect::<Vec<_>>(), self.stack);
             }
             
-            match inst {
-                // program flow
-                VmInstruction::Label(..) => (),
-                VmInstruction::Jump(lbl) => {
-                    i = labels[lbl];
+            match self.step(&chunk, i, &mut activation.handlers) {
+                Ok(Step::Next) => activation.ip += 1,
+                Ok(Step::Jump(target)) => activation.ip = target,
+                Ok(Step::Call(callee)) => {
+                    activation.ip += 1;
+                    activations.push(callee);
                 }
-                VmInstruction::JumpIf(lbl) => {
-                    let (cond, cond_ptr) = self.pop_stack();
-                    if cond.boolify() {
-                        i = labels[lbl];
-                    }
-                    self.memory.free(cond_ptr);
-                }
-                VmInstruction::JumpNotIf(lbl) => {
-                    let (cond, cond_ptr) = self.pop_stack();
-                    if !cond.boolify() {
-                        i = labels[lbl];
-                    }
-                    self.memory.free(cond_ptr);
-                }
-                
-                // operations
-                VmInstruction::Call(arg_count) => {
-                    let mut args = Vec::new();
-                    for _ in 0..*arg_count {
-                        args.push(self.pop_stack_ptr());
-                    }
-                    args.reverse();
+                Ok(Step::Return(ptr)) => {
+                    // leave any scopes and loop values the body still had open
+                    self.scope.env = activation.start_env;
+                    self.stack.truncate(activation.start_stack);
                     
-                    let func_ptr = self.pop_stack_ptr();
-                    self.memory.add_ref(func_ptr);
-                    
-                    let out_ptr = {
-                        let func = self.memory.get_mut(func_ptr).clone();
-                        match &func {
-                            Value::Function(Function::Vm { body, params }) => {
-                                self.scope.new_layer();
-                                for (i, param) in params.iter().enumerate() {
-                                    let arg = *args.get(i).unwrap_or(&self.memory.alloc(Value::Null));
-                                    self.scope.decl_var(&mut self.memory, param.name.clone(), arg);
-                                }
-                                self.run_instructions(body)?.unwrap_or(self.memory.alloc(Value::Null))
-                            }
-                            Value::Function(Function::Builtin(BuiltinFunction::Return)) => {
-                                let ptr = args.first().copied();
-                                
-                                // add ref to stop from being freed
-                                if let Some(ptr) = &ptr {
-                                    self.memory.add_ref(*ptr);
-                                }
-                                
-                                // free scope layers
-                                for _ in start_scope_layers..self.scope.layers.len() {
-                                    let layer = &self.scope.pop_layer();
-                                    self.scope.free_layer(&mut self.memory, layer);
-                                }
-                                
-                                // rm ref
-                                if let Some(ptr) = &ptr {
-                                    self.memory.rm_ref(*ptr);
-                                }
-                                
-                                // free any spare arguments
-                                for arg in &args[1..] {
-                                    self.memory.free(*arg);
-                                }
-                                
-                                return Ok(ptr);
-                            }
-                            _ => {
-                                func.call(&mut self.logs, &mut self.memory, &args)?
-                            }
-                        }
-                    };
-                    
-                    self.stack.push(out_ptr);
-                    
-                    self.memory.rm_ref(func_ptr);
-                    self.memory.free(func_ptr);
-                    for arg in args {
-                        self.memory.free(arg);
+                    let done = activations.pop().unwrap();
+                    if let Some(exit) = self.finish_activation(&done, Some(ptr)) {
+                        return Ok(exit);
                     }
                 }
-                VmInstruction::CallEv(name) => {
-                    self.run_event_target(&EventTarget::Global { name: name.clone() })?;
-                }
-                VmInstruction::Unary(op) => {
-                    let (right, right_ptr) = self.pop_stack();
-                    
-                    let right = right.clone();
-                    
-                    self.push_stack_alloc(match op {
-                        UnaryOp::Minus =>
-                            Value::Num { data: -right.numbify() },
-                        UnaryOp::Number =>
-                            Value::Num { data: right.numbify() },
-                        
-                        UnaryOp::Invert =>
-                            Value::Bool { data: !right.boolify() },
-                        UnaryOp::Boolify =>
-                            Value::Bool { data: right.boolify() }
-                    });
-                    
-                    self.memory.free(right_ptr);
+                
+                Err(err) => {
+                    self.unwind(&mut activations, err)?;
                 }
-                VmInstruction::Binary(op) => {
-                    let (right, right_ptr) = self.pop_stack();
-                    let right = right.clone();
-                    let (left, left_ptr) = self.pop_stack();
-                    let left = left.clone();
-                    
-                    let val = self.run_binary_op(&left, &right, op);
-                    self.push_stack_alloc(val);
-                    
-                    self.memory.free(right_ptr);
-                    self.memory.free(left_ptr);
+            }
+            
+            if let Some(wake) = self.suspending.take() {
+                // the event takes everything it had on the vm with it,
+                // the host is left with the env it had before the event entered its own
+                let callers = std::mem::take(&mut self.scope.callers);
+                let env = std::mem::replace(&mut self.scope.env, callers.first().copied().unwrap_or(self.scope.global));
+                
+                self.routines.push(Routine {
+                    activations,
+                    stack: std::mem::take(&mut self.stack),
+                    env,
+                    callers,
+                    frames: std::mem::take(&mut self.frames),
+                    wake
+                });
+                return Ok(Exit::Suspended);
+            }
+        }
+    }
+    // hands what a call returned back to its caller, or gives it back if it was the bottom activation
+    fn finish_activation(&mut self, done: &Activation, out: Option<MemPointer>) -> Option<Exit> {
+        let Some(base) = done.base else {
+            return Some(Exit::Returned(out));
+        };
+        
+        self.frames.pop();
+        self.scope.leave();
+        
+        let out = out.unwrap_or_else(|| self.memory.alloc(Value::Null));
+        self.stack.truncate(base);
+        self.push_stack_ptr(out);
+        None
+    }
+    // finds the innermost try that can catch err, leaving every call it passes on the way
+    fn unwind(&mut self, activations: &mut Vec<Activation>, err: Error) -> Result<(), Error> {
+        while let Some(activation) = activations.last_mut() {
+            let handler = if err.is_catchable() { activation.handlers.pop() } else { None };
+            if let Some(handler) = handler {
+                // put everything back how it was when the try started
+                self.stack.truncate(handler.stack_len);
+                self.scope.env = handler.env;
+                self.scope.callers.truncate(handler.callers);
+                self.frames.truncate(handler.frames);
+                
+                let value = self.error_value(err);
+                self.push_stack_ptr(value);
+                activation.ip = handler.catch;
+                return Ok(());
+            }
+            
+            // the bottom activation's frame and scope are left by whoever set them up
+            if activations.pop().unwrap().base.is_none() {
+                break;
+            }
+            self.frames.pop();
+            self.scope.leave();
+        }
+        
+        Err(err)
+    }
+    #[allow(clippy::too_many_lines)]
+    fn step(&mut self, chunk: &Chunk, i: usize, handlers: &mut Vec<TryHandler>) -> Result<Step, Error> {
+        let inst = &chunk.instructions[i];
+        
+        match inst {
+            // program flow
+            VmInstruction::Label(..) => (),
+            VmInstruction::Jump(target) => {
+                return Ok(Step::Jump(*target));
+            }
+            VmInstruction::JumpIf(target)
+            | VmInstruction::JumpNotIf(target) => {
+                let (cond, _) = self.pop_stack();
+                let cond = cond.boolify();
+                
+                if cond == matches!(inst, VmInstruction::JumpIf(..)) {
+                    return Ok(Step::Jump(*target));
                 }
-                VmInstruction::Prop
-                | VmInstruction::PropNoFree => {
-                    let (key, key_ptr) = self.pop_stack();
-                    let key = key.clone();
-                    let (obj, obj_ptr) = self.pop_stack();
-                    let obj = obj.clone();
-                    
-                    let ptr = obj.get_item(&mut self.memory, &key);
-                    self.push_stack_ptr(ptr);
-                    
-                    if let VmInstruction::Prop = inst {
-                        self.memory.free(key_ptr);
-                        self.memory.free(obj_ptr);
-                    }
+            }
+            VmInstruction::Try(catch) => {
+                handlers.push(TryHandler {
+                    catch: *catch,
+                    stack_len: self.stack.len(),
+                    env: self.scope.env,
+                    callers: self.scope.callers.len(),
+                    frames: self.frames.len()
+                });
+            }
+            VmInstruction::EndTry => {
+                handlers.pop();
+            }
+            VmInstruction::Throw => {
+                let ptr = self.pop_stack_ptr();
+                let message = self.memory.get(ptr).stringify(&self.memory);
+                
+                return Err(self.trace_error(Error::Thrown { value: ptr, message, range: None }, chunk.span(i)));
+            }
+            VmInstruction::Return => {
+                return Ok(Step::Return(self.pop_stack_ptr()));
+            }
+            VmInstruction::JumpIfNoPop(target)
+            | VmInstruction::JumpNotIfNoPop(target) => {
+                let ptr = *self.stack.last().unwrap();
+                let cond = self.memory.get(ptr).boolify();
+                
+                if cond == matches!(inst, VmInstruction::JumpIfNoPop(..)) {
+                    return Ok(Step::Jump(*target));
                 }
-                VmInstruction::Len
-                | VmInstruction::LenNoFree => {
-                    let (obj, obj_ptr) = self.pop_stack();
-                    
-                    let val = Value::Num {
-                        data: obj.length() as f32
+            }
+            
+            // operations
+            VmInstruction::Call(arg_count) => {
+                // the function and its args stay on the stack while it runs,
+                // so a builtin calling back into the vm cant have them collected
+                let (start, args) = self.call_args(arg_count);
+                let base = start - 1;
+                let func_ptr = self.stack[base];
+                
+                return self.call_step(func_ptr, &args, chunk.span(i), base);
+            }
+            VmInstruction::Method(name, arg_count) => {
+                let (start, mut args) = self.call_args(arg_count);
+                let base = start - 1;
+                let receiver = self.memory.get(self.stack[base]);
+                
+                let Some((func_ptr, bound)) = self.find_method(receiver, name) else {
+                    let err = Error::UnknownMethod {
+                        method: name.clone(),
+                        type_name: receiver.type_of(&self.memory).to_string(),
+                        range: None
                     };
-                    self.push_stack_alloc(val);
-                    
-                    if let VmInstruction::Len = inst {
-                        self.memory.free(obj_ptr);
-                    }
+                    return Err(self.trace_error(err, chunk.span(i)));
+                };
+                // methods get the receiver as their first arg
+                if bound {
+                    args.insert(0, self.stack[base]);
                 }
-                VmInstruction::Arrify => {
-                    let (obj, obj_ptr) = self.pop_stack();
-                    let obj = obj.clone();
-                    
-                    let items = obj.arrify(&mut self.memory);
-                    for item in &items {
-                        self.memory.add_ref(*item);
-                    }
-                    
-                    let val = Value::Arr {
-                        items
+                
+                return self.call_step(func_ptr, &args, chunk.span(i), base);
+            }
+            VmInstruction::SuperMethod(name, arg_count) => {
+                let (start, mut args) = self.call_args(arg_count);
+                let base = start - 2;
+                let parent = self.stack[base];
+                
+                let Some(func_ptr) = class_method(&self.memory, parent, name) else {
+                    let err = Error::UnknownMethod {
+                        method: name.clone(),
+                        type_name: String::from("super"),
+                        range: None
                     };
-                    self.push_stack_alloc(val);
-                    
-                    self.memory.free(obj_ptr);
-                }
+                    return Err(self.trace_error(err, chunk.span(i)));
+                };
                 
-                // values
-                VmInstruction::Null => {
+                // self goes first
+                args.insert(0, self.stack[base + 1]);
+                return self.call_step(func_ptr, &args, chunk.span(i), base);
+            }
+            VmInstruction::Import(path) => {
+                let exports = self.import(path, chunk.span(i))?;
+                self.push_stack_ptr(exports);
+            }
+            VmInstruction::CallEv(name) => {
+                let out = self.run_event_target_at(&EventTarget::Global { name: name.clone() }, chunk.span(i), &[])?;
+                if let Some(ptr) = out {
+                    self.push_stack_ptr(ptr);
+                } else {
                     self.push_stack_alloc(Value::Null);
                 }
-                VmInstruction::Str(str) => {
-                    self.push_stack_alloc(Value::Str { data: str.clone() });
+            }
+            VmInstruction::Unary(op) => {
+                let (right, _) = self.pop_stack();
+                
+                let right = right.clone();
+                
+                self.push_stack_alloc(match op {
+                    UnaryOp::Minus =>
+                        Value::Num { data: -right.numbify() },
+                    UnaryOp::Number =>
+                        Value::Num { data: right.numbify() },
+                    
+                    UnaryOp::Invert =>
+                        Value::Bool { data: !right.boolify() },
+                    UnaryOp::Boolify =>
+                        Value::Bool { data: right.boolify() }
+                });
+            }
+            VmInstruction::Binary(op) => {
+                let (right, _) = self.pop_stack();
+                let right = right.clone();
+                let (left, _) = self.pop_stack();
+                let left = left.clone();
+                
+                let val = self.run_binary_op(&left, &right, op);
+                self.push_stack_alloc(val);
+            }
+            VmInstruction::Prop => {
+                let (key, _) = self.pop_stack();
+                let key = key.clone();
+                let (obj, _) = self.pop_stack();
+                let obj = obj.clone();
+                
+                let ptr = obj.get_item(&mut self.memory, &key);
+                self.push_stack_ptr(ptr);
+            }
+            VmInstruction::Len => {
+                let (obj, _) = self.pop_stack();
+                
+                let val = Value::Num {
+                    data: obj.length() as f64
+                };
+                self.push_stack_alloc(val);
+            }
+            VmInstruction::Arrify => {
+                let (obj, _) = self.pop_stack();
+                let obj = obj.clone();
+                
+                let items = obj.arrify(&mut self.memory);
+                
+                let val = Value::Arr {
+                    items
+                };
+                self.push_stack_alloc(val);
+            }
+            VmInstruction::ArrExtend => {
+                let (source, _) = self.pop_stack();
+                let source = source.clone();
+                let extra = source.arrify(&mut self.memory);
+                
+                let ptr = *self.stack.last().unwrap();
+                if let Value::Arr { items } = self.memory.get_mut(ptr) {
+                    items.extend(extra);
                 }
-                VmInstruction::Num(num) => {
-                    self.push_stack_alloc(Value::Num { data: *num });
+            }
+            VmInstruction::ObjExtend => {
+                let (source, _) = self.pop_stack();
+                let extra = if let Value::Obj { data, .. } = source {
+                    data.clone()
+                } else {
+                    HashMap::new()
+                };
+                
+                let ptr = *self.stack.last().unwrap();
+                if let Value::Obj { data, .. } = self.memory.get_mut(ptr) {
+                    data.extend(extra);
                 }
-                VmInstruction::Percentage(num) => {
-                    self.push_stack_alloc(Value::Percentage { data: *num });
+            }
+            VmInstruction::SliceFrom(start) => {
+                let (obj, _) = self.pop_stack();
+                let obj = obj.clone();
+                
+                let items = obj.arrify(&mut self.memory);
+                let items = items.get(*start..).map(<[MemPointer]>::to_vec).unwrap_or_default();
+                
+                self.push_stack_alloc(Value::Arr {
+                    items
+                });
+            }
+            VmInstruction::Without(keys) => {
+                let (obj, _) = self.pop_stack();
+                let mut data = if let Value::Obj { data, .. } = obj {
+                    data.clone()
+                } else {
+                    HashMap::new()
+                };
+                data.retain(|key, _| !keys.contains(key));
+                
+                self.push_stack_alloc(Value::Obj {
+                    data,
+                    class: None
+                });
+            }
+            VmInstruction::IsType(name) => {
+                let ptr = self.pop_stack_ptr();
+                let data = self.memory.get(ptr).is_type(&self.memory, name);
+                self.push_stack_alloc(Value::Bool { data });
+            }
+            VmInstruction::HasKey(key) => {
+                let (value, _) = self.pop_stack();
+                let data = matches!(value, Value::Obj { data, .. } if data.contains_key(key));
+                self.push_stack_alloc(Value::Bool { data });
+            }
+            VmInstruction::Concat(len) => {
+                let start = self.stack.len() - len;
+                let data = self.stack[start..]
+                    .iter()
+                    .map(|ptr| self.memory.get(*ptr).stringify(&self.memory))
+                    .collect::<String>();
+                self.stack.truncate(start);
+                
+                self.push_stack_alloc(Value::Str { data });
+            }
+            
+            // values
+            VmInstruction::Null => {
+                self.push_stack_alloc(Value::Null);
+            }
+            VmInstruction::Const(idx) => {
+                self.push_stack_alloc(self.constants[*idx].clone());
+            }
+            VmInstruction::Bool(bool) => {
+                self.push_stack_alloc(Value::Bool { data: *bool });
+            }
+            VmInstruction::Func { body, names, params, name } => {
+                self.push_stack_alloc(Value::Function(Function::Vm {
+                    body: body.clone(),
+                    names: Rc::clone(names),
+                    params: *params,
+                    name: name.clone(),
+                    env: self.scope.env
+                }));
+            },
+            VmInstruction::Arr { len } => {
+                let mut items = Vec::new();
+                
+                for _i in 0..*len {
+                    items.push(self.pop_stack_ptr());
                 }
-                VmInstruction::Bool(bool) => {
-                    self.push_stack_alloc(Value::Bool { data: *bool });
+                
+                items.reverse();
+                
+                self.push_stack_alloc(Value::Arr {
+                    items
+                });
+            }
+            VmInstruction::Obj { keys } => {
+                let mut values = Vec::new();
+                for _key in keys {
+                    values.push(self.pop_stack_ptr());
                 }
-                VmInstruction::Func { body, args } => {
-                    self.push_stack_alloc(Value::Function(Function::Vm {
-                        body: body.clone(),
-                        params: args.clone()
-                    }));
-                },
-                VmInstruction::Arr { len } => {
-                    let mut items = Vec::new();
-                    
-                    for _i in 0..*len {
-                        let ptr = self.pop_stack_ptr();
-                        self.memory.add_ref(ptr);
-                        items.push(ptr);
-                    }
-                    
-                    items.reverse();
-                    
-                    self.push_stack_alloc(Value::Arr {
-                        items
-                    });
+                values.reverse();
+                
+                let mut map = HashMap::new();
+                for (i, key) in keys.iter().enumerate() {
+                    map.insert(key.clone(), values[i]);
                 }
-                VmInstruction::Obj { keys } => {
-                    let mut values = Vec::new();
-                    for _key in keys {
-                        values.push(self.pop_stack_ptr());
-                    }
-                    values.reverse();
+                
+                self.push_stack_alloc(Value::Obj {
+                    data: map,
+                    class: None
+                });
+            }
+            
+            VmInstruction::Class { name, methods } => {
+                let funcs = self.stack.split_off(self.stack.len() - methods.len());
+                let parent = self.pop_stack_ptr();
+                
+                let parent = match self.memory.get(parent) {
+                    Value::Null => None,
+                    Value::Class { .. } => Some(parent),
                     
-                    let mut map = HashMap::new();
-                    for (i, key) in keys.iter().enumerate() {
-                        if let Some(already) = map.get(&key.clone()) {
-                            self.memory.rm_ref(*already);
-                            self.memory.free(*already);
-                        }
-                        let ptr = values[i];
-                        self.memory.add_ref(ptr);
-                        map.insert(key.clone(), ptr);
+                    other => {
+                        let err = Error::CannotExtend {
+                            type_name: other.type_of(&self.memory).to_string(),
+                            range: None
+                        };
+                        return Err(self.trace_error(err, chunk.span(i)));
                     }
-                    
-                    
-                    self.push_stack_alloc(Value::Obj {
-                        data: map
-                    });
-                }
-                VmInstruction::Color(color) => {
-                    self.push_stack_alloc(Value::Color {
-                        data: *color
-                    });
-                }
+                };
                 
-                // scope
-                VmInstruction::Get(name) => {
-                    let var = self.scope.get_var(name);
-                    if let Some(ptr) = var {
-                        self.push_stack_ptr(ptr);
-                    } else {
-                        self.push_stack_alloc(Value::Null);
-                    }
-                }
-                VmInstruction::Decl(name) => {
-                    let ptr = self.pop_stack_ptr();
-                    self.scope.decl_var(&mut self.memory, name.clone(), ptr);
+                self.push_stack_alloc(Value::Class {
+                    name: name.clone(),
+                    methods: methods.iter().cloned().zip(funcs).collect(),
+                    parent
+                });
+            }
+            
+            // scope
+            VmInstruction::Get(var) => {
+                let var = self.scope.get(&self.memory, *var);
+                if let Some(ptr) = var {
                     self.push_stack_ptr(ptr);
+                } else {
+                    self.push_stack_alloc(Value::Null);
                 }
-                VmInstruction::NewScope => {
-                    self.scope.new_layer();
-                }
-                VmInstruction::PopScope => {
-                    let layer =self.scope.pop_layer();
-                    
-                    self.scope.free_layer(&mut self.memory, &layer);
+            }
+            VmInstruction::Decl(var) => {
+                let ptr = self.pop_stack_ptr();
+                self.scope.decl(&mut self.memory, *var, ptr);
+                self.push_stack_ptr(ptr);
+            }
+            VmInstruction::NewScope(names) => {
+                self.scope.new_layer(&mut self.memory, names);
+            }
+            VmInstruction::PopScope => {
+                self.scope.pop_layer(&self.memory);
+            }
+            
+            // assignments
+            VmInstruction::AsiVar(var, op) => {
+                let ptr = self.scope.get(&self.memory, *var).unwrap_or_else(|| self.memory.alloc(Value::Null));
+                let (val, val_ptr) = self.pop_stack();
+                let val = val.clone();
+                if let AssignmentOp::Default = op {
+                    let new_val_ptr = self.memory.alloc(val);
+                    self.scope.set(&mut self.memory, *var, new_val_ptr);
+                } else {
+                    let original = self.memory.get(ptr).clone();
+                    self.memory.get_cell_mut(ptr).val = self.run_binary_op(&original, &val, &op.clone().into());
                 }
+                self.push_stack_ptr(val_ptr);
+            }
+            VmInstruction::AsiProp(op) => {
+                let (key, _) = self.pop_stack();
+                let key = key.clone();
                 
-                // assignments
-                VmInstruction::AsiVar(name, op) => {
-                    let ptr = self.scope.get_var(name).unwrap_or(self.memory.alloc(Value::Null));
-                    let (val, val_ptr) = self.pop_stack();
-                    let val = val.clone();
-                    if let AssignmentOp::Default = op {
-                        let new_val_ptr = self.memory.alloc(val);
-                        self.scope.set_var(&mut self.memory, name.clone(), new_val_ptr);
-                    } else {
-                        let original = self.memory.get(ptr).clone();
-                        self.memory.get_cell_mut(ptr).val = self.run_binary_op(&original, &val, &op.clone().into());
-                    }
-                    self.push_stack_ptr(val_ptr);
-                }
-                VmInstruction::AsiProp(op) => {
-                    let (key, key_ptr) = self.pop_stack();
-                    let key = key.clone();
-                    
-                    let (obj, obj_ptr) = self.pop_stack();
-                    
-                    let index_key = match &obj {
-                        Value::Obj { ..} => IndexKey::Str(key.stringify(&self.memory)),
-                        _ => IndexKey::Int(key.numbify() as usize),
+                let (obj, obj_ptr) = self.pop_stack();
+                
+                let index_key = match &obj {
+                    Value::Obj { ..} => IndexKey::Str(key.stringify(&self.memory)),
+                    _ => IndexKey::Int(key.numbify() as usize),
+                };
+                
+                let (val, val_ptr) = self.pop_stack();
+                let val = val.clone();
+                
+                //let _obj = self.memory.get_mut(obj_ptr);
+                
+                if let AssignmentOp::Default = op {
+                    self.memory.get_mut(obj_ptr).set_item(index_key, val_ptr);
+                } else {
+                    let ptr = {
+                        let obj = self.memory.get(obj_ptr).clone();
+                        obj.get_item(&mut self.memory, &key)
                     };
                     
-                    let (val, val_ptr) = self.pop_stack();
-                    let val = val.clone();
-                    self.memory.add_ref(val_ptr);
-                    
-                    //let _obj = self.memory.get_mut(obj_ptr);
-                    
-                    if let AssignmentOp::Default = op {
-                        let old_ptr = {
-                            let mut obj = self.memory.get_mut(obj_ptr);
-                            obj.set_item(index_key, val_ptr)
-                        };
-                        
-                        if let Some(ptr) = old_ptr {
-                            self.memory.free(ptr);
-                        }
-                    } else {
-                        let ptr = {
-                            let obj = self.memory.get(obj_ptr).clone();
-                            obj.get_item(&mut self.memory, &key)
-                        };
-                        
-                        let original = self.memory.get(ptr).clone();
-                        let val = self.run_binary_op(&original, &val, &op.clone().into());
-                        let val_ptr = self.memory.alloc(val);
-                        
-                        let old_ptr = {
-                            let obj = self.memory.get_mut(obj_ptr);
-                            obj.set_item(index_key, val_ptr)
-                        };
-                        
-                        if let Some(ptr) = old_ptr {
-                            self.memory.free(ptr);
-                        }
-                    }
+                    let original = self.memory.get(ptr).clone();
+                    let val = self.run_binary_op(&original, &val, &op.clone().into());
+                    let val_ptr = self.memory.alloc(val);
                     
-                    self.memory.free(key_ptr);
-                    self.memory.free(obj_ptr);
-                    self.push_stack_ptr(val_ptr);
+                    self.memory.get_mut(obj_ptr).set_item(index_key, val_ptr);
                 }
                 
-                // stack
-                VmInstruction::Pop => {
-                    let ptr = self.pop_stack_ptr();
-                    self.memory.free(ptr);
-                }
-                VmInstruction::Dupe(idx) => {
-                    let val = self.memory.get(self.stack[self.stack.len() - 1 - idx]);
-                    let val = val.clone().dupe(&mut self.memory);
-                    self.push_stack_alloc(val);
-                }
-                VmInstruction::DupePtr(idx) => {
-                    let ptr = self.stack[self.stack.len() - 1 - idx];
-                    self.push_stack_ptr(ptr);
-                }
-                
-                _ => {
-                    print_warn!(LogSource::Rtr, "couldnt run instruction '{:?}'", inst);
-                }
+                self.push_stack_ptr(val_ptr);
             }
             
-            i += 1;
+            // stack
+            VmInstruction::Pop => {
+                self.pop_stack_ptr();
+            }
+            VmInstruction::Dupe(idx) => {
+                let val = self.memory.get(self.stack[self.stack.len() - 1 - idx]);
+                let val = val.clone().dupe(&mut self.memory);
+                self.push_stack_alloc(val);
+            }
+            VmInstruction::DupePtr(idx) => {
+                let ptr = self.stack[self.stack.len() - 1 - idx];
+                self.push_stack_ptr(ptr);
+            }
+            VmInstruction::Under(n) => {
+                let ptr = self.pop_stack_ptr();
+                self.stack.insert(self.stack.len() - n, ptr);
+            }
         }
         
-        Ok(None)
+        Ok(Step::Next)
     }
     fn run_binary_op(&mut self, left: &Value, right: &Value, op: &BinaryOp) -> Value {
-        fn get_num(left: &Value, right: &Value) -> Option<(f32, f32)> {
+        fn get_num(left: &Value, right: &Value) -> Option<(f64, f64)> {
             if let (
                 Value::Num { data: left },
                 Value::Num { data: right }
@@ -678,31 +1421,31 @@ impl RTRModule {
                 if let Some((left, right)) = get_num(left, right) {
                     Value::Num { data: left - right }
                 } else {
-                    Value::Num { data: f32::NAN }
+                    Value::Num { data: f64::NAN }
                 }
             BinaryOp::Mul =>
                 if let Some((left, right)) = get_num(left, right) {
                     Value::Num { data: left * right }
                 } else {
-                    Value::Num { data: f32::NAN }
+                    Value::Num { data: f64::NAN }
                 }
             BinaryOp::Div =>
                 if let Some((left, right)) = get_num(left, right) {
                     Value::Num { data: left / right }
                 } else {
-                    Value::Num { data: f32::NAN }
+                    Value::Num { data: f64::NAN }
                 }
             BinaryOp::Mod =>
                 if let Some((left, right)) = get_num(left, right) {
                     Value::Num { data: left % right }
                 } else {
-                    Value::Num { data: f32::NAN }
+                    Value::Num { data: f64::NAN }
                 }
             BinaryOp::Pow =>
                 if let Some((left, right)) = get_num(left, right) {
                     Value::Num { data: left.powf(right) }
                 } else {
-                    Value::Num { data: f32::NAN }
+                    Value::Num { data: f64::NAN }
                 }
             
             BinaryOp::Eql =>
@@ -727,18 +1470,120 @@ impl RTRModule {
                     _ => left.clone()
                 }
             
-            _ => todo!("{:?}", op)
+            // the compiler turns these into jumps, this is what they mean on two values
+            BinaryOp::And =>
+                if left.boolify() { right.clone() } else { left.clone() },
+            BinaryOp::Or =>
+                if left.boolify() { left.clone() } else { right.clone() }
         }
     }
     
-    #[allow(clippy::unused_self)]
-    pub fn compile_event(&self, statement: Vec<AstStatement>) -> Result<Vec<VmInstruction>, Error> {
+    pub fn compile_event(&mut self, statement: Vec<AstStatement>) -> Result<Chunk, Error> {
+        self.compile_statements(statement, None).map(Chunk::link)
+    }
+    // the top level of a file, which returns an object of its exports
+    fn compile_body(&mut self, ast: &AstProgram, file: Option<&Path>) -> Result<Chunk, Error> {
+        let mut chunk = self.compile_statements(ast.body.clone(), file)?;
+        
+        for name in &ast.exports {
+            chunk.push(VmInstruction::Get(Var::Name(self.scope.symbols.intern(name))));
+        }
+        chunk.push(VmInstruction::Obj {
+            keys: ast.exports.clone()
+        });
+        chunk.push(VmInstruction::Return);
+        
+        Ok(chunk.link())
+    }
+    fn compile_statements(&mut self, statements: Vec<AstStatement>, file: Option<&Path>) -> Result<Chunk, Error> {
         compiler::compile_statements(&mut CompileContext {
-            label_count: 0
-        }, statement)
+            label_count: 0,
+            symbols: &mut self.scope.symbols,
+            constants: &mut self.constants,
+            loops: Vec::new(),
+            scope_depth: 0,
+            scopes: Vec::new(),
+            tries: Vec::new(),
+            file
+        }, statements)
     }
 }
 
+// what a step of the vm wants to happen next
+enum Step {
+    Next,
+    Jump(usize),
+    // a function written in rtr, to be ran before carrying on
+    Call(Activation),
+    Return(MemPointer)
+}
+
+// how execute stopped
+enum Exit {
+    Returned(Option<MemPointer>),
+    Suspended
+}
+
+// what begin_call did with a call
+enum Callee {
+    Done(MemPointer),
+    Vm(Activation)
+}
+
+// a body being ran, which instruction it is on and what to put back when it is done
+#[derive(Debug)]
+struct Activation {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    // trys are local to the body, errors that get past them carry on to the caller
+    handlers: Vec<TryHandler>,
+    start_env: MemPointer,
+    start_stack: usize,
+    // where the function and its args start on the caller's stack, None for the body execute was given
+    base: Option<usize>
+}
+
+// what a suspended event is waiting for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Wake {
+    // the clock reaching this many ms
+    At(f64),
+    // the host calling signal with this id, what it gives is what the call that waited returns
+    Signal(usize)
+}
+
+// an event put aside part way through, with everything it had on the vm
+#[derive(Debug)]
+struct Routine {
+    activations: Vec<Activation>,
+    stack: Vec<MemPointer>,
+    env: MemPointer,
+    callers: Vec<MemPointer>,
+    frames: Vec<CallFrame>,
+    wake: Wake
+}
+
+impl Routine {
+    // the values it still needs, for the collector
+    fn roots(&self) -> impl Iterator<Item = MemPointer> + '_ {
+        self.stack
+            .iter()
+            .copied()
+            .chain([self.env])
+            .chain(self.callers.iter().copied())
+    }
+}
+
+// where to go and what to restore when an error reaches a try
+#[derive(Debug)]
+struct TryHandler {
+    catch: usize,
+    stack_len: usize,
+    env: MemPointer,
+    callers: usize,
+    frames: usize
+}
+
 pub enum IndexKey {
     Str(String),
     Int(usize),
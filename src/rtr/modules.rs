@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::rtr::RTRModule;
+use crate::rtr::ast::node::AstProgram;
+use crate::rtr::ast::parser::Parser;
+use crate::rtr::ast::tokenise;
+use crate::rtr::error::Error;
+use crate::rtr::runtime::shared::SharedValue;
+use crate::shared::fs::read_file;
+
+// files brought in by import, shared by every script of a document. each one is ran once in a module
+// of its own, and what it exports is copied into each script that imports it
+#[derive(Debug, Default)]
+pub struct ModuleCache {
+    modules: HashMap<PathBuf, Rc<AstProgram>>,
+    exports: HashMap<PathBuf, SharedValue>,
+    // the module each file ran in, by the id of its memory. one that is running is taken out until it is done
+    vms: HashMap<usize, Box<RTRModule>>,
+    // the modules being ran right now, to catch files importing each other
+    importing: Vec<PathBuf>,
+    // every file an import tried to read, including ones that were missing or didnt parse
+    read: Vec<PathBuf>
+}
+
+impl ModuleCache {
+    pub fn load(&mut self, path: &Path) -> Result<Rc<AstProgram>, Error> {
+        if let Some(ast) = self.modules.get(path) {
+            return Ok(Rc::clone(ast));
+        }
+        if !self.read.iter().any(|read| read == path) {
+            self.read.push(path.to_path_buf());
+        }
+        
+        let name = path.display().to_string();
+        let src = read_file(&path.to_path_buf())
+            .map_err(|reason| Error::CannotImport { path: name.clone(), reason, range: None })?;
+        let mut parser = Parser {
+            pointer: 0,
+            tokens: tokenise(&src)
+        };
+        let ast = Rc::new(parser.parse().map_err(|err| Error::InvalidImport {
+            path: name,
+            error: Box::new(err),
+            range: None
+        })?);
+        
+        self.modules.insert(path.to_path_buf(), Rc::clone(&ast));
+        Ok(ast)
+    }
+    
+    // every file that has been imported, so they can be watched along with the document.
+    // ones that failed are kept too, so fixing them is noticed
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.read.iter()
+    }
+    
+    pub fn exports(&self, path: &Path) -> Option<SharedValue> {
+        self.exports.get(path).cloned()
+    }
+    pub fn evaluated(&mut self, path: &Path, exports: SharedValue, module: Box<RTRModule>) {
+        self.exports.insert(path.to_path_buf(), exports);
+        self.put(module);
+    }
+    
+    pub fn take(&mut self, id: usize) -> Option<Box<RTRModule>> {
+        self.vms.remove(&id)
+    }
+    pub fn put(&mut self, module: Box<RTRModule>) {
+        self.vms.insert(module.memory.id, module);
+    }
+    // the modules that arent running right now
+    pub fn loaded(&self) -> impl Iterator<Item = &RTRModule> {
+        self.vms.values().map(Box::as_ref)
+    }
+    // the files importing each other, starting and ending with path, if it is already being ran
+    pub fn enter(&mut self, path: &Path) -> Result<(), Vec<String>> {
+        if let Some(start) = self.importing.iter().position(|importing| importing == path) {
+            return Err(self.importing[start..]
+                .iter()
+                .chain([&path.to_path_buf()])
+                .map(|path| path.display().to_string())
+                .collect());
+        }
+        
+        self.importing.push(path.to_path_buf());
+        Ok(())
+    }
+    pub fn leave(&mut self) {
+        self.importing.pop();
+    }
+}
+
+// where an import points, relative to the folder of the file doing the importing
+pub fn resolve(from: Option<&Path>, path: &str) -> PathBuf {
+    let path = from
+        .and_then(Path::parent)
+        .unwrap_or(Path::new("."))
+        .join(path);
+    
+    path.canonicalize().unwrap_or(path)
+}
@@ -0,0 +1,198 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use crate::rtr::RTRModule;
+use crate::rtr::error::Error;
+use crate::rtr::runtime::memory::MemPointer;
+use crate::rtr::runtime::value::{BuiltinFunction, Value};
+
+// builtins that take a script function and call it for every item of an array or object.
+// the callback gets (item, key), where key is the index for arrays
+pub fn call(builtin: &BuiltinFunction, vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    // what gets kept on the stack while the callbacks run is taken off again however it ends,
+    // as builtins are also called from outside the vm loop where nothing truncates it after
+    let base = vm.stack.len();
+    let out = run(builtin, vm, args);
+    vm.stack.truncate(base);
+    out
+}
+
+fn run(builtin: &BuiltinFunction, vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let target = arg(vm, args, 0);
+    let entries = entries(vm, target);
+    
+    // the comparator is optional, so there is nothing to fall back on
+    if let BuiltinFunction::Sort = builtin {
+        let mut items: Vec<MemPointer> = entries.iter().map(|entry| entry.1).collect();
+        sort(vm, args.get(1).copied(), &mut items)?;
+        
+        return Ok(vm.memory.alloc(Value::Arr { items }));
+    }
+    let func = arg(vm, args, 1);
+    
+    match builtin {
+        BuiltinFunction::Map => {
+            let mut out = Vec::new();
+            for (key, item) in &entries {
+                out.push(vm.call_function(func, &[*item, *key], None)?);
+                vm.stack.push(*out.last().unwrap());
+            }
+            
+            Ok(collect(vm, target, &entries, out))
+        }
+        BuiltinFunction::Filter => {
+            let mut kept = Vec::new();
+            let mut out = Vec::new();
+            for entry in &entries {
+                if test(vm, func, *entry)? {
+                    kept.push(*entry);
+                    out.push(entry.1);
+                }
+            }
+            
+            Ok(collect(vm, target, &kept, out))
+        }
+        BuiltinFunction::Reduce => {
+            let mut acc = arg(vm, args, 2);
+            for (key, item) in &entries {
+                vm.stack.push(acc);
+                acc = vm.call_function(func, &[acc, *item, *key], None)?;
+            }
+            
+            Ok(acc)
+        }
+        BuiltinFunction::Find
+        | BuiltinFunction::FindIndex => {
+            for entry in &entries {
+                if test(vm, func, *entry)? {
+                    return Ok(if let BuiltinFunction::Find = builtin { entry.1 } else { entry.0 });
+                }
+            }
+            
+            Ok(if let (BuiltinFunction::FindIndex, Value::Arr { .. }) = (builtin, vm.memory.get(target)) {
+                vm.memory.alloc(Value::Num { data: -1.0 })
+            } else {
+                vm.memory.alloc(Value::Null)
+            })
+        }
+        BuiltinFunction::ForEach => {
+            for (key, item) in &entries {
+                vm.call_function(func, &[*item, *key], None)?;
+            }
+            
+            Ok(vm.memory.alloc(Value::Null))
+        }
+        BuiltinFunction::Some
+        | BuiltinFunction::Every => {
+            // some stops at the first pass, every at the first fail
+            let wanted = matches!(builtin, BuiltinFunction::Some);
+            for entry in &entries {
+                if test(vm, func, *entry)? == wanted {
+                    return Ok(vm.memory.alloc(Value::Bool { data: wanted }));
+                }
+            }
+            
+            Ok(vm.memory.alloc(Value::Bool { data: !wanted }))
+        }
+        
+        _ => unreachable!("{builtin:?} doesnt take a callback")
+    }
+}
+
+fn arg(vm: &mut RTRModule, args: &[MemPointer], i: usize) -> MemPointer {
+    args.get(i)
+        .copied()
+        .unwrap_or_else(|| vm.memory.alloc(Value::Null))
+}
+
+// (key, item) pairs, objects in key order so callbacks run in the same order every time.
+// everything is kept on the stack so callbacks changing the target cant get them collected
+fn entries(vm: &mut RTRModule, target: MemPointer) -> Vec<(MemPointer, MemPointer)> {
+    let entries: Vec<(MemPointer, MemPointer)> = match vm.memory.get(target).clone() {
+        Value::Arr { items } => items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| (vm.memory.alloc(Value::Num { data: i as f64 }), item))
+            .collect(),
+        Value::Obj { data, .. } => {
+            let mut pairs: Vec<(String, MemPointer)> = data.into_iter().collect();
+            pairs.sort_by(|a, b| a.0.cmp(&b.0));
+            pairs
+                .into_iter()
+                .map(|(key, item)| (vm.memory.alloc(Value::Str { data: key }), item))
+                .collect()
+        }
+        
+        _ => Vec::new()
+    };
+    
+    for (key, item) in &entries {
+        vm.stack.push(*key);
+        vm.stack.push(*item);
+    }
+    
+    entries
+}
+
+// builds the same kind of value as the target out of the kept entries
+fn collect(vm: &mut RTRModule, target: MemPointer, entries: &[(MemPointer, MemPointer)], items: Vec<MemPointer>) -> MemPointer {
+    if let Value::Obj { .. } = vm.memory.get(target) {
+        let data: HashMap<String, MemPointer> = entries
+            .iter()
+            .zip(items)
+            .map(|(entry, item)| (vm.memory.get(entry.0).stringify(&vm.memory), item))
+            .collect();
+        
+        vm.memory.alloc(Value::Obj { data, class: None })
+    } else {
+        vm.memory.alloc(Value::Arr { items })
+    }
+}
+
+fn test(vm: &mut RTRModule, func: MemPointer, (key, item): (MemPointer, MemPointer)) -> Result<bool, Error> {
+    let out = vm.call_function(func, &[item, key], None)?;
+    Ok(vm.memory.get(out).boolify())
+}
+
+// merge sort, so a comparator that errors can stop it
+fn sort(vm: &mut RTRModule, func: Option<MemPointer>, items: &mut Vec<MemPointer>) -> Result<(), Error> {
+    if items.len() < 2 {
+        return Ok(());
+    }
+    
+    let mut right = items.split_off(items.len() / 2);
+    sort(vm, func, items)?;
+    sort(vm, func, &mut right)?;
+    
+    let mut merged = Vec::with_capacity(items.len() + right.len());
+    let (mut a, mut b) = (0, 0);
+    while a < items.len() && b < right.len() {
+        if compare(vm, func, right[b], items[a])? == Ordering::Less {
+            merged.push(right[b]);
+            b += 1;
+        } else {
+            merged.push(items[a]);
+            a += 1;
+        }
+    }
+    merged.extend_from_slice(&items[a..]);
+    merged.extend_from_slice(&right[b..]);
+    
+    *items = merged;
+    Ok(())
+}
+
+// a comparator returns a negative number when a goes first, without one
+// numbers sort by value and anything else by its text
+fn compare(vm: &mut RTRModule, func: Option<MemPointer>, a: MemPointer, b: MemPointer) -> Result<Ordering, Error> {
+    if let Some(func) = func {
+        let out = vm.call_function(func, &[a, b], None)?;
+        return Ok(vm.memory.get(out).numbify().partial_cmp(&0.0).unwrap_or(Ordering::Equal));
+    }
+    
+    Ok(match (vm.memory.get(a), vm.memory.get(b)) {
+        (Value::Num { data: a }, Value::Num { data: b }) =>
+            a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (a, b) =>
+            a.stringify(&vm.memory).cmp(&b.stringify(&vm.memory))
+    })
+}
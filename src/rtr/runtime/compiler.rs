@@ -1,134 +1,791 @@
+use std::path::Path;
+use std::rc::Rc;
 use crate::{Log, LogKind, print_log};
-use crate::rtr::ast::node::{AstConditionalType, AstExpression, AstStatement, BinaryOp, Parameter, PropertyKey};
+use crate::rtr::ast::node::{AssignmentOp, AstConditionalType, AstExpression, AstStatement, BinaryOp, MatchArm, MatchPattern, ObjectEntry, Pattern, PatternItem, PropertyKey, TemplatePart};
 use crate::rtr::error::Error;
 use crate::rtr::error::Error::CannotAssign;
-use crate::rtr::runtime::instruction::VmInstruction;
+use crate::rtr::modules;
+use crate::rtr::runtime::instruction::{ArgCount, Chunk, Var, VmInstruction};
 use crate::rtr::runtime::instruction::VmInstruction::AsiVar;
+use crate::rtr::runtime::symbol::{Symbol, Symbols};
+use crate::rtr::runtime::value::Value;
+use crate::shared::range::Range;
 
-pub struct CompileContext {
+pub struct CompileContext<'a> {
     pub label_count: usize,
+    pub symbols: &'a mut Symbols,
+    pub constants: &'a mut Vec<Value>,
+    pub loops: Vec<LoopContext>,
+    // how many scopes are open, so break and continue know how many to pop
+    pub scope_depth: usize,
+    // the names given slots in each scope open around what is being compiled, innermost last.
+    // unlike scope_depth these carry on into functions, which can see the scopes they were made in
+    pub scopes: Vec<Vec<Symbol>>,
+    pub tries: Vec<TryContext>,
+    // the file being compiled, imports are found relative to it
+    pub file: Option<&'a Path>
 }
 
-pub fn get_label_name(ctx: &mut CompileContext) -> String {
+// a try being compiled, break and continue leaving it have to end it and run its finally
+#[derive(Clone)]
+pub struct TryContext {
+    pub finally: Option<AstStatement>,
+    pub scope_depth: usize,
+    pub scopes: usize
+}
+
+// what break and continue need to know about a loop they might jump out of
+pub struct LoopContext {
+    pub label: Option<String>,
+    pub break_lbl: usize,
+    pub continue_lbl: usize,
+    pub break_depth: usize,
+    pub continue_depth: usize,
+    // values the loop keeps on the stack while its body runs
+    pub stack: usize,
+    // how many trys were open when the loop started
+    pub try_depth: usize
+}
+
+pub fn new_label(ctx: &mut CompileContext) -> usize {
     ctx.label_count += 1;
-    ctx.label_count.to_string()
+    ctx.label_count
+}
+pub fn constant(ctx: &mut CompileContext, value: Value) -> VmInstruction {
+    ctx.constants.push(value);
+    VmInstruction::Const(ctx.constants.len() - 1)
+}
+
+// the slot of the innermost scope that declared name, names outside of them all are looked up at runtime
+fn var(ctx: &mut CompileContext, name: &str) -> Var {
+    let name = ctx.symbols.intern(name);
+    for (depth, scope) in ctx.scopes.iter().rev().enumerate() {
+        if let Some(index) = scope.iter().rposition(|slot| *slot == name) {
+            return Var::Slot { depth, index, name };
+        }
+    }
+    Var::Name(name)
+}
+// gives name a slot in the innermost scope, declaring it again keeps the slot it has
+fn decl(ctx: &mut CompileContext, name: &str) -> Var {
+    let name = ctx.symbols.intern(name);
+    let Some(scope) = ctx.scopes.last_mut() else {
+        return Var::Name(name);
+    };
+    
+    let index = scope
+        .iter()
+        .position(|slot| *slot == name)
+        .unwrap_or_else(|| {
+            scope.push(name);
+            scope.len() - 1
+        });
+    Var::Slot { depth: 0, index, name }
+}
+// pushes a NewScope, which knows its slots once close_scope is called with where it is
+fn open_scope(ctx: &mut CompileContext, instructions: &mut Chunk) -> usize {
+    ctx.scopes.push(Vec::new());
+    instructions.push(VmInstruction::NewScope(Rc::default()));
+    instructions.len() - 1
+}
+fn close_scope(ctx: &mut CompileContext, instructions: &mut Chunk, at: usize) {
+    let names = ctx.scopes.pop().expect("closed more scopes than were opened");
+    instructions.instructions[at] = VmInstruction::NewScope(names.into());
+}
+
+// jumps past the right hand side of && or || when the left side already decided it,
+// leaving that side on the stack as the result
+fn short_circuit(op: &BinaryOp, label: usize) -> VmInstruction {
+    if let BinaryOp::Or = op {
+        VmInstruction::JumpIfNoPop(label)
+    } else {
+        VmInstruction::JumpNotIfNoPop(label)
+    }
+}
+
+// pops everything between here and the loop being jumped to, then jumps
+fn loop_control(ctx: &mut CompileContext, is_break: bool, label: Option<&String>, range: &Range) -> Result<Chunk, Error> {
+    let target = match label {
+        Some(label) => ctx.loops
+            .iter()
+            .rposition(|lp| lp.label.as_ref() == Some(label))
+            .ok_or_else(|| Error::UnknownLoopLabel {
+                label: label.clone(),
+                range: Box::new(range.clone())
+            })?,
+        None => ctx.loops
+            .len()
+            .checked_sub(1)
+            .ok_or_else(|| Error::OutsideLoop {
+                keyword: String::from(if is_break { "break" } else { "continue" }),
+                range: Box::new(range.clone())
+            })?
+    };
+    
+    let lp = &ctx.loops[target];
+    let (lbl, depth) = if is_break {
+        (lp.break_lbl, lp.break_depth)
+    } else {
+        (lp.continue_lbl, lp.continue_depth)
+    };
+    
+    let inner_stack: usize = ctx.loops[target + 1..]
+        .iter()
+        .map(|lp| lp.stack)
+        .sum();
+    let try_depth = ctx.loops[target].try_depth;
+    
+    let (mut instructions, scope_depth) = leave_tries(ctx, try_depth)?;
+    for _ in depth..scope_depth {
+        instructions.push(VmInstruction::PopScope);
+    }
+    for _ in 0..inner_stack {
+        instructions.push(VmInstruction::Pop);
+    }
+    instructions.push(VmInstruction::Jump(lbl));
+    instructions.fill_spans(range);
+    
+    Ok(instructions)
+}
+
+// ends every try opened since there were `depth` of them, innermost first, running their finally on the way out.
+// also gives back how many scopes are still open afterwards
+fn leave_tries(ctx: &mut CompileContext, depth: usize) -> Result<(Chunk, usize), Error> {
+    let mut instructions = Chunk::new();
+    let mut scope_depth = ctx.scope_depth;
+    
+    for i in (depth..ctx.tries.len()).rev() {
+        let TryContext { finally, scope_depth: try_depth, scopes } = ctx.tries[i].clone();
+        for _ in try_depth..scope_depth {
+            instructions.push(VmInstruction::PopScope);
+        }
+        scope_depth = try_depth;
+        instructions.push(VmInstruction::EndTry);
+        
+        if let Some(finally) = finally {
+            // the finally body can break too, but not through the try its in
+            let inner = ctx.tries.split_off(i);
+            // the scopes inside the try have been left too
+            let inner_scopes = ctx.scopes.split_off(scopes);
+            let depth = std::mem::replace(&mut ctx.scope_depth, try_depth);
+            let finally = compile_statement(ctx, finally);
+            ctx.tries.extend(inner);
+            ctx.scopes.extend(inner_scopes);
+            ctx.scope_depth = depth;
+            instructions.append(finally?);
+        }
+    }
+    
+    Ok((instructions, scope_depth))
+}
+
+// builds an array out of items, pouring spread ones in. without any spreads this is just Arr
+fn compile_items(ctx: &mut CompileContext, items: Vec<AstExpression>) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
+    let mut pending = 0;
+    let mut started = false;
+    
+    for item in items {
+        let AstExpression::Spread { value, .. } = item else {
+            instructions.append(compile_expression(ctx, item)?);
+            pending += 1;
+            continue;
+        };
+        
+        if !started || pending > 0 {
+            instructions.push(VmInstruction::Arr { len: pending });
+            if started {
+                instructions.push(VmInstruction::ArrExtend);
+            }
+        }
+        started = true;
+        pending = 0;
+        
+        instructions.append(compile_expression(ctx, *value)?);
+        instructions.push(VmInstruction::ArrExtend);
+    }
+    
+    if !started || pending > 0 {
+        instructions.push(VmInstruction::Arr { len: pending });
+        if started {
+            instructions.push(VmInstruction::ArrExtend);
+        }
+    }
+    
+    Ok(instructions)
+}
+// the same for objects, later keys win
+fn compile_entries(ctx: &mut CompileContext, entries: Vec<ObjectEntry>) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
+    let mut keys = Vec::new();
+    let mut started = false;
+    
+    for entry in entries {
+        let value = match entry {
+            ObjectEntry::Pair(key, value) => {
+                keys.push(key);
+                instructions.append(compile_expression(ctx, value)?);
+                continue;
+            }
+            ObjectEntry::Spread(value) => value
+        };
+        
+        if !started || !keys.is_empty() {
+            instructions.push(VmInstruction::Obj { keys: std::mem::take(&mut keys) });
+            if started {
+                instructions.push(VmInstruction::ObjExtend);
+            }
+        }
+        started = true;
+        
+        instructions.append(compile_expression(ctx, value)?);
+        instructions.push(VmInstruction::ObjExtend);
+    }
+    
+    if !started || !keys.is_empty() {
+        instructions.push(VmInstruction::Obj { keys });
+        if started {
+            instructions.push(VmInstruction::ObjExtend);
+        }
+    }
+    
+    Ok(instructions)
+}
+fn compile_args(ctx: &mut CompileContext, args: Vec<AstExpression>) -> Result<(Chunk, ArgCount), Error> {
+    if args.iter().any(|arg| matches!(arg, AstExpression::Spread { .. })) {
+        Ok((compile_items(ctx, args)?, ArgCount::Spread))
+    } else {
+        let len = args.len();
+        Ok((compile_expressions(ctx, args)?, ArgCount::Fixed(len)))
+    }
+}
+
+// binds the value on top of the stack to a pattern, leaving the value there
+fn destructure(ctx: &mut CompileContext, pattern: Pattern) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
+    
+    match pattern {
+        Pattern::Name(name) => {
+            instructions.push(VmInstruction::Decl(decl(ctx, &name)));
+        }
+        Pattern::Arr { items, rest } => {
+            let len = items.len();
+            for (i, item) in items.into_iter().enumerate() {
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(constant(ctx, Value::Num { data: i as f64 }));
+                instructions.push(VmInstruction::Prop);
+                instructions.append(destructure_item(ctx, item)?);
+            }
+            if let Some(rest) = rest {
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(VmInstruction::SliceFrom(len));
+                instructions.append(destructure(ctx, *rest)?);
+                instructions.push(VmInstruction::Pop);
+            }
+        }
+        Pattern::Obj { pairs, rest } => {
+            let mut keys = Vec::new();
+            for (key, item) in pairs {
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(constant(ctx, Value::Str { data: key.clone() }));
+                instructions.push(VmInstruction::Prop);
+                instructions.append(destructure_item(ctx, item)?);
+                keys.push(key);
+            }
+            if let Some(rest) = rest {
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(VmInstruction::Without(keys));
+                instructions.push(VmInstruction::Decl(decl(ctx, &rest)));
+                instructions.push(VmInstruction::Pop);
+            }
+        }
+    }
+    
+    Ok(instructions)
+}
+// binds the value on top of the stack, swapping it for the default if its null, then pops it
+fn destructure_item(ctx: &mut CompileContext, item: PatternItem) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
+    
+    if let Some(default) = item.default {
+        let has_value_lbl = new_label(ctx);
+        instructions.push(VmInstruction::DupePtr(0));
+        instructions.push(VmInstruction::Null);
+        instructions.push(VmInstruction::Binary(BinaryOp::Eql));
+        instructions.push(VmInstruction::JumpNotIf(has_value_lbl));
+        instructions.push(VmInstruction::Pop);
+        instructions.append(compile_expression(ctx, default)?);
+        instructions.push(VmInstruction::Label(has_value_lbl));
+    }
+    instructions.append(destructure(ctx, item.target)?);
+    instructions.push(VmInstruction::Pop);
+    
+    Ok(instructions)
+}
+
+// tries each arm in turn with the value on the stack, every arm gets its own scope for the names it binds.
+// a match expression leaves what the arm evaluated to, or null if none fit
+fn compile_match<T>(
+    ctx: &mut CompileContext,
+    value: AstExpression,
+    arms: Vec<MatchArm<T>>,
+    body: fn(&mut CompileContext, T) -> Result<Chunk, Error>,
+    yields: bool
+) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
+    let end_lbl = new_label(ctx);
+    
+    instructions.append(compile_expression(ctx, value)?);
+    for arm in arms {
+        let next_lbl = new_label(ctx);
+        let mut fails = Vec::new();
+        
+        let scope = open_scope(ctx, &mut instructions);
+        ctx.scope_depth += 1;
+        instructions.push(VmInstruction::DupePtr(0));
+        instructions.append(match_pattern(ctx, arm.pattern, 0, &mut fails)?);
+        if let Some(guard) = arm.guard {
+            instructions.append(compile_expression(ctx, guard)?);
+            instructions.push(VmInstruction::JumpNotIf(fail_label(ctx, &mut fails, 0)));
+        }
+        
+        instructions.push(VmInstruction::Pop);
+        instructions.append(body(ctx, arm.body)?);
+        ctx.scope_depth -= 1;
+        close_scope(ctx, &mut instructions, scope);
+        instructions.push(VmInstruction::PopScope);
+        instructions.push(VmInstruction::Jump(end_lbl));
+        
+        instructions.append(leave_pattern(fails, next_lbl));
+        instructions.push(VmInstruction::Label(next_lbl));
+        instructions.push(VmInstruction::PopScope);
+    }
+    
+    instructions.push(VmInstruction::Pop);
+    if yields {
+        instructions.push(VmInstruction::Null);
+    }
+    instructions.push(VmInstruction::Label(end_lbl));
+    
+    Ok(instructions)
+}
+// a label to jump to when a check fails, that pops what the pattern had on the stack by then
+fn fail_label(ctx: &mut CompileContext, fails: &mut Vec<(usize, usize)>, pops: usize) -> usize {
+    let lbl = new_label(ctx);
+    fails.push((lbl, pops));
+    lbl
+}
+fn leave_pattern(fails: Vec<(usize, usize)>, target: usize) -> Chunk {
+    let mut instructions = Chunk::new();
+    
+    for (lbl, pops) in fails {
+        instructions.push(VmInstruction::Label(lbl));
+        for _ in 0..pops {
+            instructions.push(VmInstruction::Pop);
+        }
+        instructions.push(VmInstruction::Jump(target));
+    }
+    
+    instructions
+}
+// checks the value on top of the stack against a pattern and takes it off.
+// below is how many values under it the pattern put there, which a failed check has to pop too
+fn match_pattern(ctx: &mut CompileContext, pattern: MatchPattern, below: usize, fails: &mut Vec<(usize, usize)>) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
+    
+    match pattern {
+        MatchPattern::Wildcard => {
+            instructions.push(VmInstruction::Pop);
+        }
+        MatchPattern::Bind(name) => {
+            instructions.push(VmInstruction::Decl(decl(ctx, &name)));
+            instructions.push(VmInstruction::Pop);
+        }
+        MatchPattern::Literal(value) => {
+            instructions.append(compile_expression(ctx, value)?);
+            instructions.push(VmInstruction::Binary(BinaryOp::Eql));
+            instructions.push(VmInstruction::JumpNotIf(fail_label(ctx, fails, below)));
+        }
+        MatchPattern::Type { name, bind } => {
+            if let Some(bind) = bind {
+                instructions.push(VmInstruction::Decl(decl(ctx, &bind)));
+            }
+            instructions.push(VmInstruction::DupePtr(0));
+            instructions.push(VmInstruction::IsType(name));
+            instructions.push(VmInstruction::JumpNotIf(fail_label(ctx, fails, below + 1)));
+            instructions.push(VmInstruction::Pop);
+        }
+        MatchPattern::Or(mut options) => {
+            let last = options.pop().expect("or pattern without options");
+            let ok_lbl = new_label(ctx);
+            
+            // every option but the last gets the value to itself, and moves on to the next if it doesnt fit
+            for option in options {
+                let next_lbl = new_label(ctx);
+                let mut option_fails = Vec::new();
+                
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.append(match_pattern(ctx, option, 0, &mut option_fails)?);
+                instructions.push(VmInstruction::Pop);
+                instructions.push(VmInstruction::Jump(ok_lbl));
+                
+                instructions.append(leave_pattern(option_fails, next_lbl));
+                instructions.push(VmInstruction::Label(next_lbl));
+            }
+            instructions.append(match_pattern(ctx, last, below, fails)?);
+            instructions.push(VmInstruction::Label(ok_lbl));
+        }
+        MatchPattern::Arr { items, rest } => {
+            let len = items.len();
+            
+            instructions.push(VmInstruction::DupePtr(0));
+            instructions.push(VmInstruction::IsType(String::from("arr")));
+            instructions.push(VmInstruction::JumpNotIf(fail_label(ctx, fails, below + 1)));
+            
+            instructions.push(VmInstruction::DupePtr(0));
+            instructions.push(VmInstruction::Len);
+            instructions.push(constant(ctx, Value::Num { data: len as f64 }));
+            instructions.push(VmInstruction::Binary(if rest.is_some() { BinaryOp::BiggerEql } else { BinaryOp::Eql }));
+            instructions.push(VmInstruction::JumpNotIf(fail_label(ctx, fails, below + 1)));
+            
+            for (i, item) in items.into_iter().enumerate() {
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(constant(ctx, Value::Num { data: i as f64 }));
+                instructions.push(VmInstruction::Prop);
+                instructions.append(match_pattern(ctx, item, below + 1, fails)?);
+            }
+            if let Some(rest) = rest {
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(VmInstruction::SliceFrom(len));
+                instructions.append(match_pattern(ctx, *rest, below + 1, fails)?);
+            }
+            instructions.push(VmInstruction::Pop);
+        }
+        MatchPattern::Obj { pairs, rest } => {
+            instructions.push(VmInstruction::DupePtr(0));
+            instructions.push(VmInstruction::IsType(String::from("obj")));
+            instructions.push(VmInstruction::JumpNotIf(fail_label(ctx, fails, below + 1)));
+            
+            let mut keys = Vec::new();
+            for (key, pattern) in pairs {
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(VmInstruction::HasKey(key.clone()));
+                instructions.push(VmInstruction::JumpNotIf(fail_label(ctx, fails, below + 1)));
+                
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(constant(ctx, Value::Str { data: key.clone() }));
+                instructions.push(VmInstruction::Prop);
+                instructions.append(match_pattern(ctx, pattern, below + 1, fails)?);
+                keys.push(key);
+            }
+            if let Some(rest) = rest {
+                instructions.push(VmInstruction::DupePtr(0));
+                instructions.push(VmInstruction::Without(keys));
+                instructions.append(match_pattern(ctx, *rest, below + 1, fails)?);
+            }
+            instructions.push(VmInstruction::Pop);
+        }
+    }
+    
+    Ok(instructions)
+}
+
+// lets a function know what it was called, for stack traces
+fn name_function(chunk: &mut Chunk, func_name: &str) {
+    if let Some(VmInstruction::Func { name: name @ None, .. }) = chunk.instructions.last_mut() {
+        *name = Some(String::from(func_name));
+    }
 }
 
-pub fn compile_statements(ctx: &mut CompileContext, statements: Vec<AstStatement>) -> Result<Vec<VmInstruction>, Error> {
-    let mut instructions = Vec::new();
+pub fn compile_statements(ctx: &mut CompileContext, statements: Vec<AstStatement>) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
     
     for statement in statements {
-        instructions.append(compile_statement(ctx, statement)?.as_mut());
+        instructions.append(compile_statement(ctx, statement)?);
     }
     
     Ok(instructions)
 }
-pub fn compile_statement(ctx: &mut CompileContext, statement: AstStatement) -> Result<Vec<VmInstruction>, Error> {
-    let mut instructions = Vec::new();
+#[allow(clippy::too_many_lines)]
+pub fn compile_statement(ctx: &mut CompileContext, statement: AstStatement) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
     
     match statement {
         AstStatement::Block { body } => {
-            instructions.push(VmInstruction::NewScope);
-            instructions.append(compile_statements(ctx, body)?.as_mut());
+            let scope = open_scope(ctx, &mut instructions);
+            ctx.scope_depth += 1;
+            instructions.append(compile_statements(ctx, body)?);
+            ctx.scope_depth -= 1;
+            close_scope(ctx, &mut instructions, scope);
             instructions.push(VmInstruction::PopScope);
         }
-        AstStatement::Branch { cond, body, elifs, else_body, .. } => {
+        AstStatement::Branch { cond, body, elifs, else_body, range } => {
             let has_else = !elifs.is_empty() || else_body.is_some();
             
-            let end_lbl = get_label_name(ctx);
-            let mut else_lbl = if has_else { get_label_name(ctx) } else { end_lbl.clone() };
+            let end_lbl = new_label(ctx);
+            let mut else_lbl = if has_else { new_label(ctx) } else { end_lbl };
             
-            instructions.append(compile_expression(ctx, cond)?.as_mut());
-            instructions.push(VmInstruction::JumpNotIf(else_lbl.clone()));
-            instructions.append(compile_statement(ctx, *body)?.as_mut());
+            instructions.append(compile_expression(ctx, cond)?);
+            instructions.push(VmInstruction::JumpNotIf(else_lbl));
+            instructions.append(compile_statement(ctx, *body)?);
             if has_else {
-                instructions.push(VmInstruction::Jump(end_lbl.clone()));
+                instructions.push(VmInstruction::Jump(end_lbl));
             }
             
             for (i, elif) in elifs.iter().enumerate() {
                 let is_last = i == elifs.len() && else_body.is_none();
                 
-                instructions.push(VmInstruction::Label(else_lbl.clone()));
-                else_lbl = if is_last { end_lbl.clone() } else { get_label_name(ctx) };
+                instructions.push(VmInstruction::Label(else_lbl));
+                else_lbl = if is_last { end_lbl } else { new_label(ctx) };
                 
-                instructions.append(compile_expression(ctx, elif.0.clone())?.as_mut());
-                instructions.push(VmInstruction::JumpNotIf(else_lbl.clone()));
-                instructions.append(compile_statement(ctx, elif.1.clone())?.as_mut());
+                instructions.append(compile_expression(ctx, elif.0.clone())?);
+                instructions.push(VmInstruction::JumpNotIf(else_lbl));
+                instructions.append(compile_statement(ctx, elif.1.clone())?);
                 
                 if !is_last {
-                    instructions.push(VmInstruction::Jump(end_lbl.clone()));
+                    instructions.push(VmInstruction::Jump(end_lbl));
                 }
             }
             
             if let Some(body) = else_body {
                 instructions.push(VmInstruction::Label(else_lbl));
-                instructions.append(compile_statement(ctx, *body)?.as_mut());
+                instructions.append(compile_statement(ctx, *body)?);
             }
             
             instructions.push(VmInstruction::Label(end_lbl));
+            instructions.fill_spans(&range);
         }
-        AstStatement::ConditionalLoop { kind, cond, body, .. } => {
-            let start_lbl = get_label_name(ctx);
-            let end_lbl = get_label_name(ctx);
+        AstStatement::Match { value, arms, range } => {
+            instructions.append(compile_match(ctx, value, arms, compile_statement, false)?);
+            instructions.fill_spans(&range);
+        }
+        AstStatement::ConditionalLoop { kind, cond, body, label, range } => {
+            let start_lbl = new_label(ctx);
+            let end_lbl = new_label(ctx);
             
-            instructions.push(VmInstruction::Label(start_lbl.clone()));
-            instructions.append(compile_expression(ctx, cond)?.as_mut());
+            instructions.push(VmInstruction::Label(start_lbl));
+            instructions.append(compile_expression(ctx, cond)?);
             instructions.push(match kind {
-                AstConditionalType::While => VmInstruction::JumpNotIf(end_lbl.clone()),
-                AstConditionalType::Until => VmInstruction::JumpIf(end_lbl.clone())
+                AstConditionalType::While => VmInstruction::JumpNotIf(end_lbl),
+                AstConditionalType::Until => VmInstruction::JumpIf(end_lbl)
+            });
+            ctx.loops.push(LoopContext {
+                label,
+                break_lbl: end_lbl,
+                continue_lbl: start_lbl,
+                break_depth: ctx.scope_depth,
+                continue_depth: ctx.scope_depth,
+                stack: 0,
+                try_depth: ctx.tries.len()
             });
-            instructions.append(compile_statement(ctx, *body)?.as_mut());
+            let body = compile_statement(ctx, *body);
+            ctx.loops.pop();
+            instructions.append(body?);
             instructions.push(VmInstruction::Jump(start_lbl));
             instructions.push(VmInstruction::Label(end_lbl));
+            instructions.fill_spans(&range);
         }
-        AstStatement::Repeat { amount, body, .. } => {
-            let start_lbl = get_label_name(ctx);
-            let end_lbl = get_label_name(ctx);
+        AstStatement::Repeat { amount, body, label, range } => {
+            let start_lbl = new_label(ctx);
+            let continue_lbl = new_label(ctx);
+            let end_lbl = new_label(ctx);
             
-            instructions.push(VmInstruction::Num(1.0));
-            instructions.push(VmInstruction::Label(start_lbl.clone()));
+            instructions.push(constant(ctx, Value::Num { data: 1.0 }));
+            instructions.push(VmInstruction::Label(start_lbl));
             instructions.push(VmInstruction::Dupe(0));
-            instructions.append(compile_expression(ctx, amount)?.as_mut());
+            instructions.append(compile_expression(ctx, amount)?);
             instructions.push(VmInstruction::Binary(BinaryOp::Bigger));
-            instructions.push(VmInstruction::JumpIf(end_lbl.clone()));
-            instructions.append(compile_statement(ctx, *body)?.as_mut());
-            instructions.push(VmInstruction::Num(1.0));
+            instructions.push(VmInstruction::JumpIf(end_lbl));
+            ctx.loops.push(LoopContext {
+                label,
+                break_lbl: end_lbl,
+                continue_lbl,
+                break_depth: ctx.scope_depth,
+                continue_depth: ctx.scope_depth,
+                stack: 1,
+                try_depth: ctx.tries.len()
+            });
+            let body = compile_statement(ctx, *body);
+            ctx.loops.pop();
+            instructions.append(body?);
+            instructions.push(VmInstruction::Label(continue_lbl));
+            instructions.push(constant(ctx, Value::Num { data: 1.0 }));
             instructions.push(VmInstruction::Binary(BinaryOp::Add));
             instructions.push(VmInstruction::Jump(start_lbl));
             instructions.push(VmInstruction::Label(end_lbl));
             instructions.push(VmInstruction::Pop);
+            instructions.fill_spans(&range);
         }
-        AstStatement::For { var, iterator, body, .. } => {
-            let start_lbl = get_label_name(ctx);
-            let end_lbl = get_label_name(ctx);
+        AstStatement::For { var, iterator, body, label, range } => {
+            let start_lbl = new_label(ctx);
+            let continue_lbl = new_label(ctx);
+            let end_lbl = new_label(ctx);
+            let zero = constant(ctx, Value::Num { data: 0.0 });
+            let one = constant(ctx, Value::Num { data: 1.0 });
             
-            instructions.append(compile_expression(ctx, iterator)?.as_mut());
+            instructions.append(compile_expression(ctx, iterator)?);
             instructions.push(VmInstruction::Arrify);
-            instructions.push(VmInstruction::Num(0.0));                 // index
-            instructions.push(VmInstruction::Label(start_lbl.clone()));
+            instructions.push(zero);                                    // index
+            instructions.push(VmInstruction::Label(start_lbl));
             instructions.push(VmInstruction::DupePtr(1));               // array.clone()
-            instructions.push(VmInstruction::LenNoFree);                // array.len (no free)
+            instructions.push(VmInstruction::Len);                      // array.len
             instructions.push(VmInstruction::Dupe(1));                  // index.clone()
             instructions.push(VmInstruction::Binary(BinaryOp::SmallerEql)); // index < array.len
-            instructions.push(VmInstruction::JumpIf(end_lbl.clone()));  // jump if index < len
+            instructions.push(VmInstruction::JumpIf(end_lbl));          // jump if index < len
             instructions.push(VmInstruction::DupePtr(1));               // array.clone()
             instructions.push(VmInstruction::DupePtr(1));               // index.clone()
-            instructions.push(VmInstruction::PropNoFree);               // array[index]
-            instructions.push(VmInstruction::NewScope);                 // {
-            instructions.push(VmInstruction::Decl(var));                // var = array[index]
+            instructions.push(VmInstruction::Prop);                     // array[index]
+            let scope = open_scope(ctx, &mut instructions);             // {
+            instructions.push(VmInstruction::Decl(decl(ctx, &var)));    // var = array[index]
             instructions.push(VmInstruction::Pop);                      // pop output
-            instructions.append(compile_statement(ctx, *body)?.as_mut()); // <body>
+            ctx.loops.push(LoopContext {
+                label,
+                break_lbl: end_lbl,
+                continue_lbl,
+                break_depth: ctx.scope_depth,
+                continue_depth: ctx.scope_depth + 1,
+                stack: 2, // array and index
+                try_depth: ctx.tries.len()
+            });
+            ctx.scope_depth += 1;
+            let body = compile_statement(ctx, *body);
+            ctx.scope_depth -= 1;
+            ctx.loops.pop();
+            instructions.append(body?);                                 // <body>
+            close_scope(ctx, &mut instructions, scope);
+            instructions.push(VmInstruction::Label(continue_lbl));
             instructions.push(VmInstruction::PopScope);                 // }
-            instructions.push(VmInstruction::Num(1.0));                 // 1
+            instructions.push(one);                                     // 1
             instructions.push(VmInstruction::Binary(BinaryOp::Add));    // index + 1
             instructions.push(VmInstruction::Jump(start_lbl));          // jump back
             instructions.push(VmInstruction::Label(end_lbl));
             instructions.push(VmInstruction::Pop);
             instructions.push(VmInstruction::Pop);
+            instructions.fill_spans(&range);
+        }
+        AstStatement::Try { body, catch, finally, range } => {
+            let catch_lbl = new_label(ctx);
+            let done_lbl = new_label(ctx);
+            let finally = finally.map(|finally| *finally);
+            
+            instructions.push(VmInstruction::Try(catch_lbl));
+            ctx.tries.push(TryContext { finally: finally.clone(), scope_depth: ctx.scope_depth, scopes: ctx.scopes.len() });
+            let body = compile_statement(ctx, *body);
+            ctx.tries.pop();
+            instructions.append(body?);
+            instructions.push(VmInstruction::EndTry);
+            instructions.push(VmInstruction::Jump(done_lbl));
+            
+            // the error is on the stack here
+            instructions.push(VmInstruction::Label(catch_lbl));
+            if let Some((name, catch_body)) = catch {
+                let rethrow_lbl = new_label(ctx);
+                
+                let scope = open_scope(ctx, &mut instructions);
+                ctx.scope_depth += 1;
+                if let Some(name) = name {
+                    instructions.push(VmInstruction::Decl(decl(ctx, &name)));
+                }
+                instructions.push(VmInstruction::Pop);
+                
+                // an error in the catch still has to run the finally before carrying on up
+                if finally.is_some() {
+                    instructions.push(VmInstruction::Try(rethrow_lbl));
+                    ctx.tries.push(TryContext { finally: finally.clone(), scope_depth: ctx.scope_depth, scopes: ctx.scopes.len() });
+                }
+                let catch_body = compile_statement(ctx, *catch_body);
+                if finally.is_some() {
+                    ctx.tries.pop();
+                }
+                instructions.append(catch_body?);
+                if finally.is_some() {
+                    instructions.push(VmInstruction::EndTry);
+                }
+                
+                ctx.scope_depth -= 1;
+                close_scope(ctx, &mut instructions, scope);
+                instructions.push(VmInstruction::PopScope);
+                instructions.push(VmInstruction::Jump(done_lbl));
+                
+                if let Some(finally) = &finally {
+                    instructions.push(VmInstruction::Label(rethrow_lbl));
+                    instructions.push(VmInstruction::PopScope);
+                    instructions.append(compile_statement(ctx, finally.clone())?);
+                    instructions.push(VmInstruction::Throw);
+                }
+            } else if let Some(finally) = &finally {
+                instructions.append(compile_statement(ctx, finally.clone())?);
+                instructions.push(VmInstruction::Throw);
+            }
+            
+            instructions.push(VmInstruction::Label(done_lbl));
+            if let Some(finally) = finally {
+                instructions.append(compile_statement(ctx, finally)?);
+            }
+            instructions.fill_spans(&range);
+        }
+        AstStatement::Throw { value, range } => {
+            instructions.append(compile_expression(ctx, value)?);
+            instructions.push(VmInstruction::Throw);
+            instructions.fill_spans(&range);
+        }
+        
+        AstStatement::Return { value, range } => {
+            if let Some(value) = value {
+                instructions.append(compile_expression(ctx, value)?);
+            } else {
+                instructions.push(VmInstruction::Null);
+            }
+            // the vm drops any scopes and loop values left, only the finallys need running
+            instructions.append(leave_tries(ctx, 0)?.0);
+            instructions.push(VmInstruction::Return);
+            instructions.fill_spans(&range);
+        }
+        
+        AstStatement::Class { name, parent, methods, range } => {
+            if let Some(parent) = parent {
+                instructions.append(compile_expression(ctx, parent)?);
+            } else {
+                instructions.push(VmInstruction::Null);
+            }
+            
+            // methods close over a scope holding the parent as super
+            let scope = open_scope(ctx, &mut instructions);
+            instructions.push(VmInstruction::Decl(decl(ctx, "super")));
+            let mut names = Vec::new();
+            for (method, func) in methods {
+                let mut func = compile_expression(ctx, func)?;
+                name_function(&mut func, &format!("{name}.{method}"));
+                instructions.append(func);
+                names.push(method);
+            }
+            instructions.push(VmInstruction::Class {
+                name: name.clone(),
+                methods: names
+            });
+            close_scope(ctx, &mut instructions, scope);
+            instructions.push(VmInstruction::PopScope);
+            
+            instructions.push(VmInstruction::Decl(decl(ctx, &name)));
+            instructions.push(VmInstruction::Pop);
+            instructions.fill_spans(&range);
+        }
+        
+        AstStatement::Import { path, name, range } => {
+            instructions.push(VmInstruction::Import(modules::resolve(ctx.file, &path)));
+            instructions.push(VmInstruction::Decl(decl(ctx, &name)));
+            instructions.push(VmInstruction::Pop);
+            instructions.fill_spans(&range);
+        }
+        AstStatement::Break { label, range } => {
+            instructions.append(loop_control(ctx, true, label.as_ref(), &range)?);
+        }
+        AstStatement::Continue { label, range } => {
+            instructions.append(loop_control(ctx, false, label.as_ref(), &range)?);
         }
         
         AstStatement::Expression(expr) => {
-            instructions.append(compile_expression(ctx, expr)?.as_mut());
+            instructions.append(compile_expression(ctx, expr)?);
             instructions.push(VmInstruction::Pop);
         }
     }
@@ -136,79 +793,180 @@ pub fn compile_statement(ctx: &mut CompileContext, statement: AstStatement) -> R
     Ok(instructions)
 }
 
-pub fn compile_expressions(ctx: &mut CompileContext, expressions: Vec<AstExpression>) -> Result<Vec<VmInstruction>, Error> {
-    let mut instructions = Vec::new();
+pub fn compile_expressions(ctx: &mut CompileContext, expressions: Vec<AstExpression>) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
     
     for expression in expressions {
-        instructions.append(compile_expression(ctx, expression)?.as_mut());
+        instructions.append(compile_expression(ctx, expression)?);
     }
     
     Ok(instructions)
 }
 #[allow(clippy::too_many_lines)]
-pub fn compile_expression(ctx: &mut CompileContext, expression: AstExpression) -> Result<Vec<VmInstruction>, Error> {
-    let mut instructions = Vec::new();
+pub fn compile_expression(ctx: &mut CompileContext, expression: AstExpression) -> Result<Chunk, Error> {
+    let mut instructions = Chunk::new();
+    let range = expression.range().clone();
     
     match expression {
         // operations
         AstExpression::Call { args, func, .. } => {
-            let len = args.len();
-            instructions.append(compile_expression(ctx, *func)?.as_mut());
-            instructions.append(compile_expressions(ctx, args)?.as_mut());
-            instructions.push(VmInstruction::Call(len));
+            match *func {
+                // `super.name(args)` calls the parent class's method on self
+                AstExpression::Property { obj, key: PropertyKey::Str(name), .. }
+                    if matches!(&*obj, AstExpression::Variable { name, .. } if name == "super") => {
+                        instructions.push(VmInstruction::Get(var(ctx, "super")));
+                        instructions.push(VmInstruction::Get(var(ctx, "self")));
+                        let (args, count) = compile_args(ctx, args)?;
+                        instructions.append(args);
+                        instructions.push(VmInstruction::SuperMethod(name, count));
+                    }
+                // `value.name(args)` looks for name on the value first, then on its type's prototype
+                AstExpression::Property { obj, key: PropertyKey::Str(name), .. } => {
+                    instructions.append(compile_expression(ctx, *obj)?);
+                    let (args, count) = compile_args(ctx, args)?;
+                    instructions.append(args);
+                    instructions.push(VmInstruction::Method(name, count));
+                }
+                func => {
+                    instructions.append(compile_expression(ctx, func)?);
+                    let (args, count) = compile_args(ctx, args)?;
+                    instructions.append(args);
+                    instructions.push(VmInstruction::Call(count));
+                }
+            }
         }
-        AstExpression::CallEvent { target } => {
+        AstExpression::CallEvent { target, .. } => {
             instructions.push(VmInstruction::CallEv(target));
-            instructions.push(VmInstruction::Null);
         }
         AstExpression::Declare { name, value, .. } => {
-            instructions.append(compile_expression(ctx, *value)?.as_mut());
-            instructions.push(VmInstruction::Decl(name));
+            // the slot is given first so a function can find itself through it,
+            // until it is declared the name still finds whatever it did before
+            let var = decl(ctx, &name);
+            let mut value = compile_expression(ctx, *value)?;
+            name_function(&mut value, &name);
+            instructions.append(value);
+            instructions.push(VmInstruction::Decl(var));
+        }
+        AstExpression::Destructure { pattern, value, .. } => {
+            instructions.append(compile_expression(ctx, *value)?);
+            instructions.append(destructure(ctx, pattern)?);
+        }
+        // the object and key are worked out once, then kept under the value for both the read and the store
+        AstExpression::Assignment { op: op @ (AssignmentOp::And | AssignmentOp::Or), target, value, .. }
+            if matches!(*target, AstExpression::Property { .. }) => {
+            let AstExpression::Property { obj, key, .. } = *target else {
+                unreachable!()
+            };
+            let logical = if let AssignmentOp::And = op { BinaryOp::And } else { BinaryOp::Or };
+            let skip_lbl = new_label(ctx);
+            let end_lbl = new_label(ctx);
+            
+            instructions.append(compile_expression(ctx, *obj)?);
+            match key {
+                PropertyKey::Str(name) => {
+                    instructions.push(constant(ctx, Value::Str { data: name }));
+                }
+                PropertyKey::Expr(expr) => {
+                    instructions.append(compile_expression(ctx, *expr)?);
+                }
+            }
+            instructions.push(VmInstruction::DupePtr(1));
+            instructions.push(VmInstruction::DupePtr(1));
+            instructions.push(VmInstruction::Prop);
+            instructions.push(short_circuit(&logical, skip_lbl));
+            instructions.push(VmInstruction::Pop);
+            
+            instructions.append(compile_expression(ctx, *value)?);
+            instructions.push(VmInstruction::Under(2));
+            instructions.push(VmInstruction::AsiProp(AssignmentOp::Default));
+            instructions.push(VmInstruction::Jump(end_lbl));
+            
+            // the value it already had stays, the object and key under it go
+            instructions.push(VmInstruction::Label(skip_lbl));
+            instructions.push(VmInstruction::Under(2));
+            instructions.push(VmInstruction::Pop);
+            instructions.push(VmInstruction::Pop);
+            instructions.push(VmInstruction::Label(end_lbl));
         }
         AstExpression::Assignment { op, target, value, .. } => {
-            instructions.append(compile_expression(ctx, *value)?.as_mut());
+            // `a &&= b` only assigns when `a` doesnt already decide the result
+            let logical = match op {
+                AssignmentOp::And => Some(BinaryOp::And),
+                AssignmentOp::Or => Some(BinaryOp::Or),
+                _ => None
+            };
+            let end_lbl = new_label(ctx);
+            
+            let op = if let Some(logical) = &logical {
+                instructions.append(compile_expression(ctx, (*target).clone())?);
+                instructions.push(short_circuit(logical, end_lbl));
+                instructions.push(VmInstruction::Pop);
+                AssignmentOp::Default
+            } else {
+                op
+            };
+            
+            let mut value = compile_expression(ctx, *value)?;
+            if let AstExpression::Variable { name, .. } = target.as_ref() {
+                name_function(&mut value, name);
+            }
+            instructions.append(value);
             
             match *target {
                 AstExpression::Variable { name, .. } => {
-                    instructions.push(AsiVar(name, op));
+                    instructions.push(AsiVar(var(ctx, &name), op));
                 }
                 AstExpression::Property { obj, key, .. } => {
-                    instructions.append(compile_expression(ctx, *obj)?.as_mut());
+                    instructions.append(compile_expression(ctx, *obj)?);
                     match key {
                         PropertyKey::Str(name) => {
-                            instructions.push(VmInstruction::Str(name));
+                            instructions.push(constant(ctx, Value::Str { data: name }));
                         }
                         PropertyKey::Expr(expr) => {
-                            instructions.append(compile_expression(ctx, *expr)?.as_mut());
+                            instructions.append(compile_expression(ctx, *expr)?);
                         }
                     }
                     instructions.push(VmInstruction::AsiProp(op));
                 }
                 
-                _ => {
+                target => {
                     return Err(CannotAssign {
-                        to: String::from("non-variable or property")
+                        to: String::from("non-variable or property"),
+                        range: Box::new(target.range().clone())
                     })
                 }
             }
+            
+            if logical.is_some() {
+                instructions.push(VmInstruction::Label(end_lbl));
+            }
         }
         AstExpression::Unary { value, op, .. } => {
-            instructions.append(compile_expression(ctx, *value)?.as_mut());
+            instructions.append(compile_expression(ctx, *value)?);
             instructions.push(VmInstruction::Unary(op));
         }
+        AstExpression::Binary { left, right, op: op @ (BinaryOp::And | BinaryOp::Or), .. } => {
+            let end_lbl = new_label(ctx);
+            
+            instructions.append(compile_expression(ctx, *left)?);
+            instructions.push(short_circuit(&op, end_lbl));
+            instructions.push(VmInstruction::Pop);
+            instructions.append(compile_expression(ctx, *right)?);
+            instructions.push(VmInstruction::Label(end_lbl));
+        }
         AstExpression::Binary { left, right, op, .. } => {
-            instructions.append(compile_expression(ctx, *left)?.as_mut());
-            instructions.append(compile_expression(ctx, *right)?.as_mut());
+            instructions.append(compile_expression(ctx, *left)?);
+            instructions.append(compile_expression(ctx, *right)?);
             instructions.push(VmInstruction::Binary(op));
         }
         AstExpression::Property { obj, key, .. } => {
-            instructions.append(compile_expression(ctx, *obj)?.as_mut());
+            instructions.append(compile_expression(ctx, *obj)?);
             match key {
                 PropertyKey::Str(name) => {
-                    instructions.push(VmInstruction::Str(name));
+                    instructions.push(constant(ctx, Value::Str { data: name }));
                 }
                 PropertyKey::Expr(expr) => {
-                    instructions.append(compile_expression(ctx, *expr)?.as_mut());
+                    instructions.append(compile_expression(ctx, *expr)?);
                 }
             }
             instructions.push(VmInstruction::Prop);
@@ -216,49 +974,77 @@ pub fn compile_expression(ctx: &mut CompileContext, expression: AstExpression) -
         
         // accessing
         AstExpression::Variable { name, .. } => {
-            instructions.push(VmInstruction::Get(name));
+            instructions.push(VmInstruction::Get(var(ctx, &name)));
         },
         
         // values
         AstExpression::String { content, .. } => {
-            instructions.push(VmInstruction::Str(content));
+            instructions.push(constant(ctx, Value::Str { data: content }));
+        }
+        AstExpression::Template { parts, .. } => {
+            let len = parts.len();
+            for part in parts {
+                match part {
+                    TemplatePart::Str(content) =>
+                        instructions.push(constant(ctx, Value::Str { data: content })),
+                    TemplatePart::Expr(expr) =>
+                        instructions.append(compile_expression(ctx, expr)?)
+                }
+            }
+            instructions.push(VmInstruction::Concat(len));
         }
         AstExpression::Number { content, .. } => {
-            instructions.push(VmInstruction::Num(content));
+            instructions.push(constant(ctx, Value::Num { data: content }));
         }
         AstExpression::Percentage { content, .. } => {
-            instructions.push(VmInstruction::Percentage(content));
+            instructions.push(constant(ctx, Value::Percentage { data: content }));
         }
         AstExpression::Func { params, body, .. } => {
+            // a function body cant break out of the loop it was made in
+            let loops = std::mem::take(&mut ctx.loops);
+            let scope_depth = std::mem::take(&mut ctx.scope_depth);
+            let tries = std::mem::take(&mut ctx.tries);
+            // the params are the first slots of the scope the call makes
+            let params: Vec<Symbol> = params
+                .iter()
+                .map(|name| ctx.symbols.intern(name))
+                .collect();
+            let len = params.len();
+            ctx.scopes.push(params);
+            let body = compile_statement(ctx, *body);
+            let names = ctx.scopes.pop().expect("the function's scope is still open");
+            ctx.loops = loops;
+            ctx.scope_depth = scope_depth;
+            ctx.tries = tries;
+            
             instructions.push(VmInstruction::Func {
-                body: compile_statement(ctx, *body)?,
-                args: params
-                    .iter()
-                    .map(|name| Parameter { name: name.clone() })
-                    .collect()
+                body: Rc::new(body?.link()),
+                names: names.into(),
+                params: len,
+                name: None
             });
         }
         AstExpression::Array { items, .. } => {
-            let len = items.len();
-            instructions.append(compile_expressions(ctx, items)?.as_mut());
-            instructions.push(VmInstruction::Arr {
-                len
-            });
+            instructions.append(compile_items(ctx, items)?);
         }
         AstExpression::Object { pairs, .. } => {
-            let mut keys = Vec::new();
-            for pair in pairs {
-                keys.push(pair.0);
-                instructions.append(compile_expression(ctx, pair.1)?.as_mut());
-            }
-            instructions.push(VmInstruction::Obj {
-                keys
+            instructions.append(compile_entries(ctx, pairs)?);
+        }
+        AstExpression::Match { value, arms, range } => {
+            instructions.append(compile_match(ctx, *value, arms, compile_expression, true)?);
+            instructions.fill_spans(&range);
+        }
+        AstExpression::Spread { range, .. } => {
+            return Err(Error::MisplacedSpread {
+                range: Box::new(range)
             });
         }
         AstExpression::Color { content, .. } => {
-            instructions.push(VmInstruction::Color(content));
+            instructions.push(constant(ctx, Value::Color { data: content }));
         }
     }
     
+    instructions.fill_spans(&range);
+    
     Ok(instructions)
 }
\ No newline at end of file
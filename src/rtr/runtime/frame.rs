@@ -0,0 +1,35 @@
+use std::fmt::{Display, Formatter};
+use crate::shared::range::Range;
+
+#[derive(Debug, Clone)]
+pub enum FrameKind {
+    Event,
+    Function,
+    Module
+}
+
+// something that is currently running, kept around so errors can say how they got there
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub kind: FrameKind,
+    pub name: Option<String>,
+    pub call_site: Option<Range>
+}
+
+impl Display for CallFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = self.name.as_deref().unwrap_or("<anonymous>");
+        
+        match self.kind {
+            FrameKind::Event => write!(f, "in event '{name}'")?,
+            FrameKind::Function => write!(f, "in function '{name}'")?,
+            FrameKind::Module => write!(f, "in module '{name}'")?
+        }
+        
+        if let Some(site) = &self.call_site {
+            write!(f, ", called at {}", site.start)?;
+        }
+        
+        Ok(())
+    }
+}
@@ -1,34 +1,148 @@
-use crate::rtr::ast::node::{AssignmentOp, BinaryOp, Parameter, UnaryOp};
-use crate::shared::color::Color;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::rtr::ast::node::{AssignmentOp, BinaryOp, UnaryOp};
+use crate::rtr::runtime::symbol::Symbol;
+use crate::shared::range::Range;
+
+// compiled instructions, along with where in the source each one came from
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub instructions: Vec<VmInstruction>,
+    pub spans: Vec<Option<Range>>
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk::default()
+    }
+    
+    pub fn push(&mut self, instruction: VmInstruction) {
+        self.instructions.push(instruction);
+        self.spans.push(None);
+    }
+    pub fn append(&mut self, mut other: Chunk) {
+        self.instructions.append(&mut other.instructions);
+        self.spans.append(&mut other.spans);
+    }
+    
+    // gives every instruction that doesnt have a span yet this range,
+    // so the innermost expression is the one that gets reported
+    pub fn fill_spans(&mut self, range: &Range) {
+        for span in &mut self.spans {
+            if span.is_none() {
+                *span = Some(range.clone());
+            }
+        }
+    }
+    pub fn span(&self, i: usize) -> Option<&Range> {
+        self.spans.get(i)?.as_ref()
+    }
+    
+    // strips out labels and points jumps at the index of the instruction they land on
+    pub fn link(self) -> Chunk {
+        let mut offsets: HashMap<usize, usize> = HashMap::new();
+        let mut offset = 0;
+        for instruction in &self.instructions {
+            if let VmInstruction::Label(label) = instruction {
+                offsets.insert(*label, offset);
+            } else {
+                offset += 1;
+            }
+        }
+        
+        let mut linked = Chunk::new();
+        for (instruction, span) in self.instructions.into_iter().zip(self.spans) {
+            linked.instructions.push(match instruction {
+                VmInstruction::Label(..) => continue,
+                
+                VmInstruction::Jump(label) => VmInstruction::Jump(offsets[&label]),
+                VmInstruction::JumpIf(label) => VmInstruction::JumpIf(offsets[&label]),
+                VmInstruction::JumpNotIf(label) => VmInstruction::JumpNotIf(offsets[&label]),
+                VmInstruction::JumpIfNoPop(label) => VmInstruction::JumpIfNoPop(offsets[&label]),
+                VmInstruction::JumpNotIfNoPop(label) => VmInstruction::JumpNotIfNoPop(offsets[&label]),
+                VmInstruction::Try(label) => VmInstruction::Try(offsets[&label]),
+                
+                instruction => instruction
+            });
+            linked.spans.push(span);
+        }
+        
+        linked
+    }
+    
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+}
+
+// where a variable lives, worked out when compiling
+#[derive(Debug, Clone, Copy)]
+pub enum Var {
+    // a slot in the env depth layers up from the current one.
+    // until it has been declared the name is looked up like any other
+    Slot {
+        depth: usize,
+        index: usize,
+        name: Symbol
+    },
+    // looked up by name, for globals and names nothing around them declared
+    Name(Symbol)
+}
+
+// how many args a call takes off the stack
+#[derive(Debug, Clone)]
+pub enum ArgCount {
+    Fixed(usize),
+    // a single array holding the args, for calls that spread something in
+    Spread
+}
 
 #[derive(Debug, Clone)]
 pub enum VmInstruction {
-    // program flow
-    Label(String),
-    Jump(String),
-    JumpIf(String),
-    JumpNotIf(String),
+    // program flow (labels only exist until the chunk is linked, then jumps hold offsets)
+    Label(usize),
+    Jump(usize),
+    JumpIf(usize),
+    JumpNotIf(usize),
+    JumpIfNoPop(usize),    // (used in && and ||)
+    JumpNotIfNoPop(usize),
+    Try(usize),            // errors until the matching EndTry jump here with the error on the stack
+    EndTry,
+    Throw,
+    Return,                // leaves the chunk with the top of the stack
     
     // operations
-    Call(usize),
+    Call(ArgCount),
+    Method(String, ArgCount),      // the receiver is below the args
+    SuperMethod(String, ArgCount), // the parent class then self are below the args
     CallEv(String),
     Unary(UnaryOp),
     Binary(BinaryOp),
     Prop,
-    PropNoFree, // (used in for)
     Len,
-    LenNoFree,  // (used in for)
     Arrify,
+    ArrExtend,             // pours the top value's items into the array below it
+    ObjExtend,             // copies the top object's keys into the object below it
+    SliceFrom(usize),      // the items of the top value from an index on, for `[a, ...rest]`
+    Without(Vec<String>),  // a copy of the top object without some keys, for `{a, ...rest}`
+    Concat(usize),         // stringifies the top n values and joins them into one string
+    IsType(String),        // whether the top value is of a type, by its typeof name or a class in its chain
+    HasKey(String),        // whether the top value is an object with the key
     
     // values
     Null,
-    Str(String),
-    Num(f32),
-    Percentage(f32),
+    Const(usize), // index into the module's constants
     Bool(bool),
     Func {
-        body: Vec<VmInstruction>,
-        args: Vec<Parameter>
+        body: Rc<Chunk>,
+        // the slots of the env the body runs in, the params first
+        names: Rc<[Symbol]>,
+        params: usize,
+        name: Option<String>
     },
     Arr {
         len: usize
@@ -36,24 +150,27 @@ pub enum VmInstruction {
     Obj {
         keys: Vec<String>
     },
-    Color(Color),
+    Class {
+        name: String,
+        methods: Vec<String> // the parent class then the method funcs are on the stack
+    },
+    
+    // modules
+    Import(PathBuf), // runs the module the first time, then pushes what it exports
     
     // scope
-    Get(String),
-    Decl(String),
-    NewScope,
+    Get(Var),
+    Decl(Var),
+    NewScope(Rc<[Symbol]>), // the names given slots in it
     PopScope,
     
     // assignments
-    AsiVar(String, AssignmentOp),
+    AsiVar(Var, AssignmentOp),
     AsiProp(AssignmentOp),
     
     // stack
     Pop,
     Dupe(usize),
     DupePtr(usize),
-    
-    // memory
-    AddRef,
-    RmRef
+    Under(usize) // moves the top value below the n under it
 }
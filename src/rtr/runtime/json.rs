@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use crate::rtr::runtime::memory::{MemPointer, Memory};
+use crate::rtr::runtime::shared::SharedValue;
+use crate::rtr::runtime::value::{format_num, TypeValue, Value};
+use crate::shared::color::Color;
+
+// how many arrays and objects can be inside each other, both ways, before it is an error
+// rather than running out of stack
+const MAX_DEPTH: usize = 128;
+
+// shared values as json, with what json cant hold written as an object with one $ key,
+// like {"$color": [255, 0, 0, 255]}, so it comes back as it went in. used by storage
+pub fn write(value: &SharedValue) -> Result<String, String> {
+    let mut writer = Writer {
+        out: String::new(),
+        indent: None,
+        tagged: true,
+        too_deep: false
+    };
+    writer.value(value, 0);
+    writer.finish()
+}
+// a value as plain json for scripts, with indent put before each line if given.
+// colors become hex strings, percentages their number, types their name and
+// functions and classes are left out of objects and are null in arrays
+pub fn stringify(memory: &Memory, ptr: MemPointer, indent: Option<String>) -> Result<String, String> {
+    let value = to_json(memory, ptr, &mut Vec::new())?.unwrap_or(SharedValue::Null);
+    
+    let mut writer = Writer {
+        out: String::new(),
+        indent: indent.filter(|indent| !indent.is_empty()),
+        tagged: false,
+        too_deep: false
+    };
+    writer.value(&value, 0);
+    writer.finish()
+}
+
+// None for what json has nothing for, path is the arrays and objects being turned into json
+fn to_json(memory: &Memory, ptr: MemPointer, path: &mut Vec<MemPointer>) -> Result<Option<SharedValue>, String> {
+    if path.contains(&ptr) {
+        return Err(String::from("cannot stringify a value that holds itself"));
+    }
+    
+    Ok(Some(match memory.get(ptr) {
+        Value::Str { data } => SharedValue::Str(data.clone()),
+        Value::Num { data } if data.is_finite() => SharedValue::Num(*data),
+        // json has no nan or infinity
+        Value::Null | Value::Num { .. } => SharedValue::Null,
+        Value::Percentage { data } => SharedValue::Num(*data),
+        Value::Bool { data } => SharedValue::Bool(*data),
+        Value::Color { data } if data.a == 255 => SharedValue::Str(data.to_hex_rgb()),
+        Value::Color { data } => SharedValue::Str(data.to_hex_rgba()),
+        Value::Type { data } => SharedValue::Str(data.to_string()),
+        
+        Value::Arr { .. } | Value::Obj { .. } if path.len() == MAX_DEPTH => {
+            return Err(format!("cannot stringify more than {MAX_DEPTH} nested arrays and objects"));
+        }
+        Value::Arr { items } => {
+            path.push(ptr);
+            let items = items
+                .iter()
+                .map(|item| Ok(to_json(memory, *item, path)?.unwrap_or(SharedValue::Null)))
+                .collect::<Result<_, String>>();
+            path.pop();
+            SharedValue::Arr(items?)
+        }
+        Value::Obj { data, .. } => {
+            path.push(ptr);
+            let mut out = HashMap::new();
+            for (key, item) in data {
+                let item = to_json(memory, *item, path);
+                match item {
+                    Ok(Some(item)) => {
+                        out.insert(key.clone(), item);
+                    }
+                    Ok(None) => (),
+                    Err(err) => {
+                        path.pop();
+                        return Err(err);
+                    }
+                }
+            }
+            path.pop();
+            SharedValue::Obj(out)
+        }
+        
+        Value::Function(..)
+        | Value::Class { .. }
+        | Value::Env { .. } => return Ok(None)
+    }))
+}
+
+struct Writer {
+    out: String,
+    indent: Option<String>,
+    // whether what json cant hold is written as tags, rather than having been turned into json already
+    tagged: bool,
+    // set once an array or object is nested deeper than MAX_DEPTH, nothing more is written after
+    too_deep: bool
+}
+
+impl Writer {
+    fn finish(self) -> Result<String, String> {
+        if self.too_deep {
+            return Err(format!("more than {MAX_DEPTH} nested arrays and objects"));
+        }
+        Ok(self.out)
+    }
+    // depth is how many arrays and objects value is inside of
+    fn value(&mut self, value: &SharedValue, depth: usize) {
+        if self.too_deep {
+            return;
+        }
+        
+        match value {
+            // handles into another module mean nothing outside of it
+            SharedValue::Null | SharedValue::Remote(..) => self.out.push_str("null"),
+            SharedValue::Bool(data) => self.out.push_str(if *data { "true" } else { "false" }),
+            SharedValue::Num(data) if data.is_finite() => self.out.push_str(&format_num(*data)),
+            SharedValue::Num(data) => self.tag("$num", &SharedValue::Str(format_num(*data)), depth),
+            SharedValue::Str(data) => self.str(data),
+            
+            SharedValue::Percentage(data) => self.tag("$percentage", &SharedValue::Num(*data), depth),
+            SharedValue::Color(data) => self.tag("$color", &SharedValue::Arr(
+                [data.r, data.g, data.b, data.a]
+                    .iter()
+                    .map(|channel| SharedValue::Num(f64::from(*channel)))
+                    .collect()
+            ), depth),
+            SharedValue::Type(data) => self.tag("$type", &SharedValue::Str(data.to_string()), depth),
+            
+            SharedValue::Arr(items) => {
+                self.open('[', depth);
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(',');
+                    }
+                    self.line(depth + 1);
+                    self.value(item, depth + 1);
+                }
+                if !items.is_empty() {
+                    self.line(depth);
+                }
+                self.out.push(']');
+            }
+            // an object that looks like a tag is wrapped, so it comes back as an object
+            SharedValue::Obj(data) if self.tagged && data.len() == 1 && data.keys().all(|key| key.starts_with('$')) => {
+                self.open('{', depth);
+                self.str("$obj");
+                self.out.push(':');
+                self.obj(data, depth + 1);
+                self.out.push('}');
+            }
+            SharedValue::Obj(data) => self.obj(data, depth)
+        }
+    }
+    // keys are sorted so the same value always gives the same text
+    fn obj(&mut self, data: &HashMap<String, SharedValue>, depth: usize) {
+        let mut keys: Vec<&String> = data.keys().collect();
+        keys.sort();
+        
+        self.open('{', depth);
+        for (i, key) in keys.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.line(depth + 1);
+            self.str(key);
+            self.out.push_str(if self.indent.is_some() { ": " } else { ":" });
+            self.value(&data[*key], depth + 1);
+        }
+        if !keys.is_empty() {
+            self.line(depth);
+        }
+        self.out.push('}');
+    }
+    fn tag(&mut self, tag: &str, value: &SharedValue, depth: usize) {
+        self.open('{', depth);
+        self.str(tag);
+        self.out.push(':');
+        self.value(value, depth + 1);
+        self.out.push('}');
+    }
+    fn open(&mut self, char: char, depth: usize) {
+        if depth >= MAX_DEPTH {
+            self.too_deep = true;
+        }
+        self.out.push(char);
+    }
+    fn line(&mut self, depth: usize) {
+        if let Some(indent) = &self.indent {
+            self.out.push('\n');
+            self.out.push_str(&indent.repeat(depth));
+        }
+    }
+    fn str(&mut self, data: &str) {
+        self.out.push('"');
+        for char in data.chars() {
+            match char {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                char if char.is_control() => {
+                    write!(self.out, "\\u{:04x}", char as u32).unwrap();
+                }
+                char => self.out.push(char)
+            }
+        }
+        self.out.push('"');
+    }
+}
+
+// the other way, undoing the tags write makes
+pub fn read(src: &str) -> Result<SharedValue, String> {
+    parse(src).map(untag)
+}
+// plain json from scripts, $ keys are left as they are
+pub fn parse(src: &str) -> Result<SharedValue, String> {
+    Reader::new(src).all()
+}
+
+struct Reader {
+    chars: Vec<char>,
+    i: usize,
+    // how many arrays and objects the reader is inside of
+    depth: usize
+}
+
+impl Reader {
+    fn new(src: &str) -> Reader {
+        Reader {
+            chars: src.chars().collect(),
+            i: 0,
+            depth: 0
+        }
+    }
+    fn all(&mut self) -> Result<SharedValue, String> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        if self.i < self.chars.len() {
+            return Err(self.error("the end"));
+        }
+        
+        Ok(value)
+    }
+    
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.i).copied()
+    }
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.i += 1;
+        }
+    }
+    // lines and columns count from 1, like the rest of the diagnostics
+    fn position(&self, i: usize) -> String {
+        let before = &self.chars[..i.min(self.chars.len())];
+        let ln = before.iter().filter(|char| **char == '\n').count() + 1;
+        let col = before.iter().rev().take_while(|char| **char != '\n').count() + 1;
+        format!("{ln}:{col}")
+    }
+    fn error(&self, wanted: &str) -> String {
+        let at = self.position(self.i);
+        match self.peek() {
+            // control characters are written as escapes so the message stays on one line
+            Some(char) if char.is_control() => format!("expected {wanted} at {at}, got '{}'", char.escape_debug()),
+            Some(char) => format!("expected {wanted} at {at}, got '{char}'"),
+            None => format!("expected {wanted} at {at}, got the end")
+        }
+    }
+    fn expect(&mut self, char: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() != Some(char) {
+            return Err(self.error(&format!("'{char}'")));
+        }
+        self.i += 1;
+        Ok(())
+    }
+    
+    fn value(&mut self) -> Result<SharedValue, String> {
+        self.skip_whitespace();
+        
+        match self.peek() {
+            Some('n') => self.word("null", SharedValue::Null),
+            Some('t') => self.word("true", SharedValue::Bool(true)),
+            Some('f') => self.word("false", SharedValue::Bool(false)),
+            Some('"') => self.string().map(SharedValue::Str),
+            Some('[') => self.nested(Reader::arr),
+            Some('{') => self.nested(Reader::obj).map(SharedValue::Obj),
+            Some(char) if char == '-' || char.is_ascii_digit() => self.num(),
+            
+            _ => Err(self.error("a value"))
+        }
+    }
+    fn nested<T>(&mut self, read: fn(&mut Reader) -> Result<T, String>) -> Result<T, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("more than {MAX_DEPTH} nested arrays and objects at {}", self.position(self.i)));
+        }
+        
+        self.depth += 1;
+        let out = read(self);
+        self.depth -= 1;
+        out
+    }
+    fn word(&mut self, word: &str, value: SharedValue) -> Result<SharedValue, String> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(&format!("'{word}'")));
+            }
+            self.i += 1;
+        }
+        Ok(value)
+    }
+    // -? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?
+    fn num(&mut self) -> Result<SharedValue, String> {
+        let start = self.i;
+        if self.peek() == Some('-') {
+            self.i += 1;
+        }
+        
+        // no leading zeros, so a 0 is the whole of the integer part
+        if self.peek() == Some('0') {
+            self.i += 1;
+        } else {
+            self.digits()?;
+        }
+        if self.peek() == Some('.') {
+            self.i += 1;
+            self.digits()?;
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.i += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.i += 1;
+            }
+            self.digits()?;
+        }
+        
+        let text: String = self.chars[start..self.i].iter().collect();
+        text.parse()
+            .map(SharedValue::Num)
+            .map_err(|_| format!("invalid number '{text}' at {}", self.position(start)))
+    }
+    fn digits(&mut self) -> Result<(), String> {
+        if !self.peek().is_some_and(|char| char.is_ascii_digit()) {
+            return Err(self.error("a digit"));
+        }
+        while self.peek().is_some_and(|char| char.is_ascii_digit()) {
+            self.i += 1;
+        }
+        Ok(())
+    }
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        
+        let mut out = String::new();
+        loop {
+            let Some(char) = self.peek() else {
+                return Err(self.error("'\"'"));
+            };
+            self.i += 1;
+            
+            match char {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some(char @ ('"' | '\\' | '/')) => char,
+                        Some('u') => {
+                            self.i += 1;
+                            out.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        
+                        _ => return Err(self.error("an escape"))
+                    };
+                    self.i += 1;
+                    out.push(escaped);
+                }
+                // json only has these as escapes
+                '\u{0}'..='\u{1f}' => {
+                    self.i -= 1;
+                    return Err(self.error("'\"' or an escape"));
+                }
+                char => out.push(char)
+            }
+        }
+    }
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let start = self.i;
+        let code = self.hex4()?;
+        
+        // characters outside the first plane come as two escapes, a high surrogate then a low one
+        if (0xDC00..0xE000).contains(&code) {
+            self.i = start;
+            return Err(self.error("a high surrogate before a low one"));
+        }
+        if (0xD800..0xDC00).contains(&code) {
+            for expected in ['\\', 'u'] {
+                if self.peek() != Some(expected) {
+                    return Err(self.error("a low surrogate escape"));
+                }
+                self.i += 1;
+            }
+            
+            let start = self.i;
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                self.i = start;
+                return Err(self.error("a low surrogate"));
+            }
+            let code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+            return Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+        
+        Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.iter().skip(self.i).take(4).collect();
+        // from_str_radix would also take a sign
+        if digits.len() != 4 || !digits.chars().all(|char| char.is_ascii_hexdigit()) {
+            return Err(self.error("4 hex digits"));
+        }
+        let code = u32::from_str_radix(&digits, 16)
+            .map_err(|_| self.error("4 hex digits"))?;
+        self.i += 4;
+        Ok(code)
+    }
+    fn arr(&mut self) -> Result<SharedValue, String> {
+        self.expect('[')?;
+        
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.i += 1;
+            return Ok(SharedValue::Arr(items));
+        }
+        
+        loop {
+            items.push(self.value()?);
+            
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.i += 1,
+                Some(']') => {
+                    self.i += 1;
+                    return Ok(SharedValue::Arr(items));
+                }
+                _ => return Err(self.error("',' or ']'"))
+            }
+        }
+    }
+    fn obj(&mut self) -> Result<HashMap<String, SharedValue>, String> {
+        self.expect('{')?;
+        
+        let mut data = HashMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.i += 1;
+            return Ok(data);
+        }
+        
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            data.insert(key, self.value()?);
+            
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.i += 1,
+                Some('}') => {
+                    self.i += 1;
+                    return Ok(data);
+                }
+                _ => return Err(self.error("',' or '}'"))
+            }
+        }
+    }
+}
+
+// turns the objects write made for what json cant hold back into what they stand for.
+// this goes from the outside in, so a wrapped object's fields are only looked at as fields
+fn untag(value: SharedValue) -> SharedValue {
+    match value {
+        SharedValue::Arr(items) => SharedValue::Arr(items.into_iter().map(untag).collect()),
+        SharedValue::Obj(data) => untag_obj(data),
+        
+        value => value
+    }
+}
+fn untag_obj(data: HashMap<String, SharedValue>) -> SharedValue {
+    let untagged = match data.iter().next() {
+        Some((tag, value)) if data.len() == 1 => match (tag.as_str(), value) {
+            ("$obj", SharedValue::Obj(inner)) => Some(untag_fields(inner.clone())),
+            ("$num", SharedValue::Str(text)) => Some(SharedValue::Num(match text.as_str() {
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                _ => f64::NAN
+            })),
+            ("$percentage", SharedValue::Num(num)) => Some(SharedValue::Percentage(*num)),
+            ("$color", SharedValue::Arr(channels)) => {
+                let channel = |i: usize| match channels.get(i) {
+                    Some(SharedValue::Num(num)) => num.clamp(0.0, 255.0) as u8,
+                    _ => 255
+                };
+                Some(SharedValue::Color(Color { r: channel(0), g: channel(1), b: channel(2), a: channel(3) }))
+            }
+            ("$type", SharedValue::Str(name)) => Some(SharedValue::Type(type_named(name))),
+            
+            _ => None
+        },
+        _ => None
+    };
+    
+    untagged.unwrap_or_else(|| untag_fields(data))
+}
+fn untag_fields(data: HashMap<String, SharedValue>) -> SharedValue {
+    SharedValue::Obj(data.into_iter().map(|(key, value)| (key, untag(value))).collect())
+}
+fn type_named(name: &str) -> TypeValue {
+    match name {
+        "type" => TypeValue::Type,
+        "null" => TypeValue::Null,
+        "str" => TypeValue::Str,
+        "num" => TypeValue::Num,
+        "percentage" => TypeValue::Percentage,
+        "bool" => TypeValue::Bool,
+        "function" => TypeValue::Function,
+        "arr" => TypeValue::Arr,
+        "obj" => TypeValue::Obj,
+        "color" => TypeValue::Color,
+        "class" => TypeValue::Class,
+        "env" => TypeValue::Env,
+        
+        class => TypeValue::Instance { class: String::from(class) }
+    }
+}
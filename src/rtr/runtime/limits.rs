@@ -0,0 +1,29 @@
+// how much a single run of a module is allowed to do, shared by every call it makes.
+// None leaves that limit off
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub instructions: Option<usize>,
+    pub call_depth: Option<usize>,
+    // live memory cells, checked after collecting
+    pub heap_cells: Option<usize>
+}
+
+impl Limits {
+    pub fn unbounded() -> Limits {
+        Limits {
+            instructions: None,
+            call_depth: None,
+            heap_cells: None
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            instructions: Some(1_000_000),
+            call_depth: Some(256),
+            heap_cells: Some(1_000_000)
+        }
+    }
+}
@@ -1,84 +1,146 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use crate::rtr::runtime::value::Value;
 
 const MEM_DEBUG: bool = false;
 
+// the heap never collects below this many live cells
+const MIN_COLLECT_THRESHOLD: usize = 1024;
+
+static NEXT_MEMORY_ID: AtomicUsize = AtomicUsize::new(0);
+
 fn debug_log(indent: u8, txt: &str) {
     if MEM_DEBUG {
         println!("{}{}", "    ".repeat(indent as usize + 1), txt);
     }
 }
 
-#[derive(Default, Debug)]
+// a mark and sweep heap, anything not reachable from the roots given to collect is freed
+#[derive(Debug)]
 pub struct Memory {
-    pub cells: Vec<Option<MemCell>>
+    // tells memories apart, so a value handed to another module knows which one it is from
+    pub id: usize,
+    pub cells: Vec<Option<MemCell>>,
+    // slots freed by the last sweeps, reused before the heap grows
+    free_list: Vec<usize>,
+    // values the host is holding onto, these count as roots for as long as they are pinned
+    pinned: HashMap<usize, usize>,
+    // collecting again once this many cells are live
+    threshold: usize,
+    
+    total_allocs: usize,
+    collections: usize,
+    last_freed: usize
 }
 
-impl Memory {
-    pub fn get_open(&mut self) -> usize {
-        self.cells.iter().position(Option::is_none)
-            .unwrap_or_else(|| {
-                self.cells.push(None);
-                self.cells.len() - 1
-            })
-    }
-    pub fn get_open_pointer(&mut self) -> MemPointer {
-        let i = self.get_open();
-        
-        MemPointer {
-            id: MemId(i)
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory {
+            id: NEXT_MEMORY_ID.fetch_add(1, Ordering::Relaxed),
+            cells: Vec::new(),
+            free_list: Vec::new(),
+            pinned: HashMap::new(),
+            threshold: MIN_COLLECT_THRESHOLD,
+            
+            total_allocs: 0,
+            collections: 0,
+            last_freed: 0
         }
     }
-    
+}
+
+impl Memory {
     // operations
     pub fn alloc(&mut self, val: Value) -> MemPointer {
-        let ptr = self.get_open_pointer();
-        
-        debug_log(0, &format!("alloc {} {:?}", ptr.id.0, val));
-        self.cells[ptr.id.0] = Some(MemCell {
+        let cell = Some(MemCell {
             val,
-            id: MemId(ptr.id.0),
-            refs: 0
+            marked: false
         });
-        ptr
-    }
-    pub fn free(&mut self, ptr: MemPointer) {
-        if !self.has_cell(ptr) {
-            debug_log(0, &format!("free {}, doesnt exist", ptr.id.0));
-            return;
-        }
         
-        debug_log(0, &format!("free {}", ptr.id.0));
+        let i = if let Some(i) = self.free_list.pop() {
+            self.cells[i] = cell;
+            i
+        } else {
+            self.cells.push(cell);
+            self.cells.len() - 1
+        };
         
-        let cell = self.get_cell(ptr);
+        self.total_allocs += 1;
+        debug_log(0, &format!("alloc {i} {:?}", self.cells[i].as_ref().unwrap().val));
         
-        debug_log(1, &format!("{:?}", cell.val));
+        MemPointer {
+            id: MemId(i)
+        }
+    }
+    
+    // host handles
+    pub fn pin(&mut self, ptr: MemPointer) {
+        *self.pinned.entry(ptr.id.0).or_default() += 1;
+    }
+    pub fn unpin(&mut self, ptr: MemPointer) {
+        if let Some(count) = self.pinned.get_mut(&ptr.id.0) {
+            *count -= 1;
+            if *count == 0 {
+                self.pinned.remove(&ptr.id.0);
+            }
+        }
+    }
+    
+    // collection
+    pub fn live(&self) -> usize {
+        self.cells.len() - self.free_list.len()
+    }
+    pub fn should_collect(&self) -> bool {
+        self.live() >= self.threshold
+    }
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = MemPointer>) {
+        let mut grey: Vec<MemPointer> = roots.into_iter().collect();
+        grey.extend(self.pinned.keys().map(|id| MemPointer { id: MemId(*id) }));
         
-        if cell.refs > 0 {
-            debug_log(1, &format!("couldnt free {}, has ref of {}", ptr.id.0, cell.refs));
-            return;
+        // mark
+        while let Some(ptr) = grey.pop() {
+            let Some(cell) = self.cells[ptr.id.0].as_mut() else {
+                continue;
+            };
+            if cell.marked {
+                continue;
+            }
+            
+            cell.marked = true;
+            cell.val.trace(&mut grey);
         }
         
-        let val = self.get(ptr).clone();
-        val.free(self);
+        // sweep
+        let mut freed = 0;
+        for (i, slot) in self.cells.iter_mut().enumerate() {
+            match slot {
+                Some(cell) if cell.marked => {
+                    cell.marked = false;
+                }
+                Some(_) => {
+                    debug_log(0, &format!("free {i}"));
+                    *slot = None;
+                    self.free_list.push(i);
+                    freed += 1;
+                }
+                None => ()
+            }
+        }
         
-        self.cells[ptr.id.0] = None;
+        self.collections += 1;
+        self.last_freed = freed;
+        self.threshold = (self.live() * 2).max(MIN_COLLECT_THRESHOLD);
+        debug_log(0, &format!("collected {freed}, {} live", self.live()));
     }
-    
-    pub fn add_ref(&mut self, ptr: MemPointer) {
-        assert!(self.has_cell(ptr), "ptr to dead cell");
-        debug_log(0, &format!("add ref {}", ptr.id.0));
-        let cell = self.cells[ptr.id.0].as_mut().unwrap();
-        debug_log(1, &format!("now {}", cell.refs + 1));
-        self.cells[ptr.id.0].as_mut().unwrap().refs += 1;
-    }
-    pub fn rm_ref(&mut self, ptr: MemPointer) {
-        assert!(self.has_cell(ptr), "ptr to dead cell");
-        debug_log(0, &format!("rm ref {}", ptr.id.0));
-        let cell = self.cells[ptr.id.0].as_mut().unwrap();
-        if cell.refs > 0 {
-            cell.refs -= 1;
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            live: self.live(),
+            capacity: self.cells.len(),
+            total_allocs: self.total_allocs,
+            collections: self.collections,
+            last_freed: self.last_freed
         }
-        debug_log(1, &format!("now {}", cell.refs));
     }
     
     // accessing
@@ -128,21 +190,29 @@ impl Memory {
 #[derive(Debug)]
 pub struct MemCell {
     pub val: Value,
-    #[allow(unused)]
-    pub id: MemId,
-    pub refs: usize
+    marked: bool
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct MemPointer {
     pub id: MemId
 }
 
-impl MemPointer {
-    pub fn free(self, memory: &mut Memory) {
-        memory.free(self);
-    }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemId (pub usize);
+
+#[derive(Debug, Clone)]
+pub struct HeapStats {
+    pub live: usize,
+    pub capacity: usize,
+    pub total_allocs: usize,
+    pub collections: usize,
+    pub last_freed: usize
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct MemId (pub usize);
+impl Display for HeapStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} live of {} cells, {} allocs, {} collections (last freed {})",
+            self.live, self.capacity, self.total_allocs, self.collections, self.last_freed)
+    }
+}
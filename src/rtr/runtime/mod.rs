@@ -4,3 +4,13 @@ pub mod memory;
 pub mod compiler;
 pub mod instruction;
 pub mod scope;
+pub mod frame;
+pub mod symbol;
+pub mod timers;
+pub mod limits;
+pub mod callbacks;
+pub mod prototype;
+pub mod shared;
+pub mod json;
+pub mod storage;
+pub mod remote;
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use crate::rtr::RTRModule;
+use crate::rtr::error::Error;
+use crate::rtr::runtime::memory::{MemPointer, Memory};
+use crate::rtr::runtime::value::{BuiltinFunction, Function, RustFunction, Value};
+use crate::shared::color::Color;
+
+// the types that have methods, `value.name(args)` looks here when the value has no key of its own called name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Prototype {
+    Str,
+    Num,
+    Arr,
+    Obj,
+    Color
+}
+
+impl Prototype {
+    pub fn of(value: &Value) -> Option<Prototype> {
+        match value {
+            Value::Str { .. } =>
+                Some(Prototype::Str),
+            Value::Num { .. } | Value::Percentage { .. } =>
+                Some(Prototype::Num),
+            Value::Arr { .. } =>
+                Some(Prototype::Arr),
+            Value::Obj { .. } =>
+                Some(Prototype::Obj),
+            Value::Color { .. } =>
+                Some(Prototype::Color),
+            
+            _ => None
+        }
+    }
+}
+
+// methods are plain functions that get the value they were called on as their first arg
+#[derive(Debug, Default)]
+pub struct Prototypes {
+    tables: HashMap<Prototype, HashMap<String, MemPointer>>
+}
+
+impl Prototypes {
+    pub fn add(&mut self, memory: &mut Memory, proto: Prototype, name: &str, func: Function) {
+        let ptr = memory.alloc(Value::Function(func));
+        self.tables
+            .entry(proto)
+            .or_default()
+            .insert(String::from(name), ptr);
+    }
+    pub fn get(&self, proto: Prototype, name: &str) -> Option<MemPointer> {
+        self.tables
+            .get(&proto)?
+            .get(name)
+            .copied()
+    }
+    
+    // the method functions, for the collector
+    pub fn roots(&self) -> impl Iterator<Item = MemPointer> + '_ {
+        self.tables
+            .values()
+            .flat_map(HashMap::values)
+            .copied()
+    }
+}
+
+// the builtins that make sense as methods, with the receiver taking the place of their first arg
+pub fn builtins(memory: &mut Memory) -> Prototypes {
+    let mut prototypes = Prototypes::default();
+    
+    let methods: [(Prototype, &[(&str, BuiltinFunction)]); 4] = [
+        (Prototype::Str, &[
+            ("length", BuiltinFunction::Length),
+            ("join", BuiltinFunction::Join),
+            ("split", BuiltinFunction::Split),
+            ("ord", BuiltinFunction::Ord),
+            ("toUpper", BuiltinFunction::ToUpper),
+            ("toLower", BuiltinFunction::ToLower),
+            ("toTitle", BuiltinFunction::ToTitle),
+            ("item", BuiltinFunction::Item)
+        ]),
+        (Prototype::Num, &[
+            ("abs", BuiltinFunction::Abs),
+            ("sqrt", BuiltinFunction::Sqrt),
+            ("round", BuiltinFunction::Round),
+            ("floor", BuiltinFunction::Floor),
+            ("ceil", BuiltinFunction::Ceil),
+            ("min", BuiltinFunction::Min),
+            ("max", BuiltinFunction::Max),
+            ("chr", BuiltinFunction::Chr)
+        ]),
+        (Prototype::Arr, &[
+            ("length", BuiltinFunction::Length),
+            ("item", BuiltinFunction::Item),
+            ("has", BuiltinFunction::Has),
+            ("keys", BuiltinFunction::Keys),
+            ("values", BuiltinFunction::Values),
+            ("map", BuiltinFunction::Map),
+            ("filter", BuiltinFunction::Filter),
+            ("reduce", BuiltinFunction::Reduce),
+            ("sort", BuiltinFunction::Sort),
+            ("find", BuiltinFunction::Find),
+            ("findIndex", BuiltinFunction::FindIndex),
+            ("forEach", BuiltinFunction::ForEach),
+            ("some", BuiltinFunction::Some),
+            ("every", BuiltinFunction::Every)
+        ]),
+        (Prototype::Obj, &[
+            ("length", BuiltinFunction::Length),
+            ("item", BuiltinFunction::Item),
+            ("has", BuiltinFunction::Has),
+            ("keys", BuiltinFunction::Keys),
+            ("values", BuiltinFunction::Values),
+            ("map", BuiltinFunction::Map),
+            ("filter", BuiltinFunction::Filter),
+            ("reduce", BuiltinFunction::Reduce),
+            ("find", BuiltinFunction::Find),
+            ("forEach", BuiltinFunction::ForEach),
+            ("some", BuiltinFunction::Some),
+            ("every", BuiltinFunction::Every)
+        ])
+    ];
+    
+    for (proto, methods) in methods {
+        for (name, builtin) in methods {
+            prototypes.add(memory, proto, name, Function::Builtin(builtin.clone()));
+        }
+    }
+    
+    // the global join concatenates its args, on an array it goes between the items
+    prototypes.add(memory, Prototype::Arr, "join", Function::Rust(arr_join));
+    
+    let color_methods: [(&str, RustFunction); 6] = [
+        ("r", color_r),
+        ("g", color_g),
+        ("b", color_b),
+        ("a", color_a),
+        ("hex", color_hex),
+        ("toString", color_to_string)
+    ];
+    for (name, func) in color_methods {
+        prototypes.add(memory, Prototype::Color, name, Function::Rust(func));
+    }
+    
+    prototypes
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn arr_join(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let sep = args
+        .get(1)
+        .map_or(String::from(","), |ptr| vm.memory.get(*ptr).stringify(&vm.memory));
+    
+    let data = if let Value::Arr { items } = vm.memory.get(args[0]) {
+        items
+            .iter()
+            .map(|ptr| vm.memory.get(*ptr).stringify(&vm.memory))
+            .collect::<Vec<String>>()
+            .join(&sep)
+    } else {
+        String::new()
+    };
+    
+    Ok(vm.memory.alloc(Value::Str { data }))
+}
+
+// colors only come in as the receiver, so there is always one to look at
+fn color(vm: &RTRModule, args: &[MemPointer]) -> Color {
+    match args.first().map(|ptr| vm.memory.get(*ptr)) {
+        Some(Value::Color { data }) => *data,
+        _ => Color::rgb(0, 0, 0)
+    }
+}
+fn channel(vm: &mut RTRModule, data: u8) -> MemPointer {
+    vm.memory.alloc(Value::Num { data: f64::from(data) })
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn color_r(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let data = color(vm, args).r;
+    Ok(channel(vm, data))
+}
+#[allow(clippy::unnecessary_wraps)]
+fn color_g(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let data = color(vm, args).g;
+    Ok(channel(vm, data))
+}
+#[allow(clippy::unnecessary_wraps)]
+fn color_b(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let data = color(vm, args).b;
+    Ok(channel(vm, data))
+}
+#[allow(clippy::unnecessary_wraps)]
+fn color_a(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let data = color(vm, args).a;
+    Ok(channel(vm, data))
+}
+// #rrggbb, with the alpha on the end when it isnt opaque
+#[allow(clippy::unnecessary_wraps)]
+fn color_hex(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let color = color(vm, args);
+    let data = if color.a == 255 { color.to_hex_rgb() } else { color.to_hex_rgba() };
+    
+    Ok(vm.memory.alloc(Value::Str { data }))
+}
+#[allow(clippy::unnecessary_wraps)]
+fn color_to_string(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    let data = Value::Color { data: color(vm, args) }.stringify(&vm.memory);
+    
+    Ok(vm.memory.alloc(Value::Str { data }))
+}
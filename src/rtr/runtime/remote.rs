@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::mem;
+use std::rc::{Rc, Weak};
+use crate::rtr::RTRModule;
+use crate::rtr::error::Error;
+use crate::rtr::runtime::instruction::Chunk;
+use crate::rtr::runtime::memory::{MemPointer, Memory};
+use crate::rtr::runtime::shared::SharedValue;
+use crate::rtr::runtime::value::{class_method, Function, Value};
+
+// a value left in the memory of the module it was made in, like a function an imported file exports
+// or a callback passed to one. that module keeps it alive for as long as something holds the handle
+pub struct Handle {
+    // the id of the memory it is in
+    module: usize,
+    ptr: MemPointer
+}
+
+impl Debug for Handle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({}, {})", self.module, self.ptr.id.0)
+    }
+}
+
+fn handle(vm: &mut RTRModule, ptr: MemPointer) -> Rc<Handle> {
+    let handle = Rc::new(Handle {
+        module: vm.memory.id,
+        ptr
+    });
+    vm.handles.push(Rc::downgrade(&handle));
+    handle
+}
+
+// what the handles a module gave out point to, forgetting the ones nothing holds anymore
+pub fn roots(handles: &mut Vec<Weak<Handle>>) -> Vec<MemPointer> {
+    handles.retain(|handle| handle.strong_count() > 0);
+    handles
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|handle| handle.ptr)
+        .collect()
+}
+
+// what cant be copied between modules
+#[derive(Debug, Clone)]
+pub enum Remote {
+    // functions and classes, which are called where they live
+    Function(Rc<Handle>),
+    // the fields are copied, the methods are called on the instance where it lives
+    Instance {
+        class: String,
+        data: HashMap<String, SharedValue>,
+        methods: Vec<(String, Rc<Handle>)>,
+        instance: Rc<Handle>
+    }
+}
+
+// a function from another module, methods also know the instance they are called on
+#[derive(Debug, Clone)]
+pub struct RemoteFunction {
+    pub func: Rc<Handle>,
+    pub receiver: Option<Rc<Handle>>
+}
+
+// copies a value out of vm for another module to use, like copy_out but with functions,
+// classes and instances left behind handles rather than becoming null
+pub fn export(vm: &mut RTRModule, ptr: MemPointer) -> SharedValue {
+    export_value(vm, ptr, &mut Vec::new())
+}
+
+// path is the arrays and objects being copied, something holding itself is cut off with null
+fn export_value(vm: &mut RTRModule, ptr: MemPointer, path: &mut Vec<MemPointer>) -> SharedValue {
+    if path.contains(&ptr) {
+        return SharedValue::Null;
+    }
+    
+    match vm.memory.get(ptr).clone() {
+        // a stand in from copy_in goes on as what it stands in for
+        Value::Function(Function::Remote(RemoteFunction { func, receiver: None })) => SharedValue::Remote(Remote::Function(func)),
+        Value::Function(..)
+        | Value::Class { .. } => SharedValue::Remote(Remote::Function(handle(vm, ptr))),
+        
+        Value::Arr { items } => {
+            path.push(ptr);
+            let items = items.iter().map(|item| export_value(vm, *item, path)).collect();
+            path.pop();
+            SharedValue::Arr(items)
+        }
+        Value::Obj { data, class: None } => {
+            path.push(ptr);
+            let data = export_fields(vm, &data, path);
+            path.pop();
+            SharedValue::Obj(data)
+        }
+        Value::Obj { data, class: Some(class) } => {
+            path.push(ptr);
+            let data = export_fields(vm, &data, path);
+            path.pop();
+            
+            let Value::Class { name, .. } = vm.memory.get(class) else {
+                return SharedValue::Obj(data);
+            };
+            let name = name.clone();
+            let methods: Vec<(String, MemPointer)> = method_names(&vm.memory, class)
+                .into_iter()
+                .filter_map(|method| class_method(&vm.memory, class, &method).map(|func| (method, func)))
+                .collect();
+            let methods = methods
+                .into_iter()
+                .map(|(method, func)| {
+                    let func = match vm.memory.get(func) {
+                        Value::Function(Function::Remote(remote)) => Some(Rc::clone(&remote.func)),
+                        _ => None
+                    }.unwrap_or_else(|| handle(vm, func));
+                    (method, func)
+                })
+                .collect();
+            let instance = stands_in_for(&vm.memory, class).unwrap_or_else(|| handle(vm, ptr));
+            
+            SharedValue::Remote(Remote::Instance {
+                class: name,
+                data,
+                methods,
+                instance
+            })
+        }
+        
+        _ => SharedValue::copy_out(&vm.memory, ptr)
+    }
+}
+fn export_fields(vm: &mut RTRModule, data: &HashMap<String, MemPointer>, path: &mut Vec<MemPointer>) -> HashMap<String, SharedValue> {
+    data.iter()
+        .map(|(key, item)| (key.clone(), export_value(vm, *item, path)))
+        .collect()
+}
+// every method a class has, including the ones it inherits
+fn method_names(memory: &Memory, class: MemPointer) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut class = Some(class);
+    while let Some(ptr) = class {
+        let Value::Class { methods, parent, .. } = memory.get(ptr) else {
+            break;
+        };
+        
+        names.extend(methods.keys().filter(|name| !names.contains(*name)).cloned().collect::<Vec<_>>());
+        class = *parent;
+    }
+    names
+}
+// the instance in another module that objects of class stand in for, if it is a class copy_in made
+fn stands_in_for(memory: &Memory, class: MemPointer) -> Option<Rc<Handle>> {
+    let Value::Class { methods, .. } = memory.get(class) else {
+        return None;
+    };
+    
+    methods.values().find_map(|method| match memory.get(*method) {
+        Value::Function(Function::Remote(RemoteFunction { receiver: Some(receiver), .. })) => Some(Rc::clone(receiver)),
+        _ => None
+    })
+}
+
+// puts what export gave into memory. functions become ones that call back into their module,
+// instances become objects of a stand in class with the same name whose methods do the same.
+// a value back in the module it came from is the value itself again
+pub fn copy_in(remote: &Remote, memory: &mut Memory) -> MemPointer {
+    match remote {
+        Remote::Function(func) if func.module == memory.id => func.ptr,
+        Remote::Function(func) => memory.alloc(Value::Function(Function::Remote(RemoteFunction {
+            func: Rc::clone(func),
+            receiver: None
+        }))),
+        
+        // the stand in may have had its fields changed
+        Remote::Instance { instance, .. } if instance.module == memory.id => {
+            sync(memory, instance.ptr, &SharedValue::Remote(remote.clone()));
+            instance.ptr
+        }
+        Remote::Instance { class, data, methods, instance } => {
+            let methods = methods
+                .iter()
+                .map(|(name, func)| {
+                    let method = Value::Function(Function::Remote(RemoteFunction {
+                        func: Rc::clone(func),
+                        receiver: Some(Rc::clone(instance))
+                    }));
+                    (name.clone(), memory.alloc(method))
+                })
+                .collect();
+            let class = memory.alloc(Value::Class {
+                name: class.clone(),
+                methods,
+                parent: None
+            });
+            
+            let data = data.iter().map(|(key, item)| (key.clone(), item.copy_in(memory))).collect();
+            memory.alloc(Value::Obj {
+                data,
+                class: Some(class)
+            })
+        }
+    }
+}
+
+// brings the array or object at ptr up to date with what another module changed its copy to.
+// the arrays and objects inside it are kept where they can be, so whatever else holds them sees it too
+fn sync(memory: &mut Memory, ptr: MemPointer, value: &SharedValue) {
+    sync_value(memory, ptr, value, &mut Vec::new());
+}
+fn sync_value(memory: &mut Memory, ptr: MemPointer, value: &SharedValue, path: &mut Vec<MemPointer>) {
+    path.push(ptr);
+    match (memory.get(ptr).clone(), value) {
+        (Value::Arr { items }, SharedValue::Arr(values)) => {
+            let items = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| sync_item(memory, items.get(i).copied(), value, path))
+                .collect();
+            *memory.get_mut(ptr) = Value::Arr { items };
+        }
+        (Value::Obj { data, class }, SharedValue::Obj(values) | SharedValue::Remote(Remote::Instance { data: values, .. })) => {
+            let data = values
+                .iter()
+                .map(|(key, value)| (key.clone(), sync_item(memory, data.get(key).copied(), value, path)))
+                .collect();
+            *memory.get_mut(ptr) = Value::Obj { data, class };
+        }
+        _ => {}
+    }
+    path.pop();
+}
+// what to keep where old was
+fn sync_item(memory: &mut Memory, old: Option<MemPointer>, value: &SharedValue, path: &mut Vec<MemPointer>) -> MemPointer {
+    let Some(old) = old else {
+        return value.copy_in(memory);
+    };
+    // something holding itself was cut off with null, it still holds itself
+    if path.contains(&old) {
+        return if let SharedValue::Null = value { old } else { value.copy_in(memory) };
+    }
+    
+    let same = match (memory.get(old), value) {
+        (Value::Arr { .. }, SharedValue::Arr(_))
+        | (Value::Obj { class: None, .. }, SharedValue::Obj(_)) => true,
+        (Value::Obj { class, .. }, SharedValue::Remote(Remote::Instance { instance, .. })) => {
+            if instance.module == memory.id {
+                instance.ptr == old
+            } else {
+                class
+                    .and_then(|class| stands_in_for(memory, class))
+                    .is_some_and(|stand_in| stand_in.module == instance.module && stand_in.ptr == instance.ptr)
+            }
+        }
+        _ => false
+    };
+    
+    if same {
+        sync_value(memory, old, value, path);
+        old
+    } else {
+        value.copy_in(memory)
+    }
+}
+
+// an error from another module, with anything pointing into its memory copied out
+enum Failed {
+    Thrown {
+        value: SharedValue,
+        message: String
+    },
+    Error(Error)
+}
+
+impl Failed {
+    fn new(vm: &mut RTRModule, err: Error) -> Failed {
+        let err = if let Error::Traced { error, .. } = err { *error } else { err };
+        match err {
+            Error::Thrown { value, message, .. } => Failed::Thrown {
+                value: export(vm, value),
+                message
+            },
+            // where it happened is in another file, the caller fills in where the call was instead
+            err => Failed::Error(err.without_range())
+        }
+    }
+    fn into_error(self, memory: &mut Memory) -> Error {
+        match self {
+            Failed::Thrown { value, message } => Error::Thrown {
+                value: value.copy_in(memory),
+                message,
+                range: None
+            },
+            Failed::Error(err) => err
+        }
+    }
+}
+
+// runs target in vm's place as if vm had called it, giving both back once it is done. it keeps to
+// vm's limits, counting its instructions and frames along with vm's, and logs after what vm logged
+fn run_in<T>(vm: &mut RTRModule, mut target: Box<RTRModule>, run: impl FnOnce(&mut RTRModule) -> T) -> (T, Box<RTRModule>) {
+    mem::swap(vm, &mut target);
+    let mut caller = target;
+    
+    let limits = mem::replace(&mut vm.limits, caller.limits.clone());
+    let modules = mem::replace(&mut vm.modules, Rc::clone(&caller.modules));
+    let outer_frames = mem::replace(&mut vm.outer_frames, caller.outer_frames + caller.frames.len());
+    vm.instructions_ran = caller.instructions_ran;
+    vm.logs = mem::take(&mut caller.logs);
+    vm.broadcasts = mem::take(&mut caller.broadcasts);
+    vm.caller = Some(caller);
+    
+    let out = run(vm);
+    
+    let mut caller = vm.caller.take().expect("the caller is put back once the module is done");
+    caller.instructions_ran = vm.instructions_ran;
+    caller.logs = mem::take(&mut vm.logs);
+    caller.broadcasts = mem::take(&mut vm.broadcasts);
+    vm.limits = limits;
+    vm.modules = modules;
+    vm.outer_frames = outer_frames;
+    
+    mem::swap(vm, &mut caller);
+    (out, caller)
+}
+
+// takes the module with the memory id out of the ones waiting on vm to finish, or the cache if it isnt
+// running. the depth it was at in the callers is given too, for put_back
+fn find(vm: &mut RTRModule, id: usize) -> Option<(Box<RTRModule>, Option<usize>)> {
+    let mut depth = 0;
+    let mut cursor = &mut vm.caller;
+    while cursor.as_ref().is_some_and(|module| module.memory.id != id) {
+        cursor = &mut cursor.as_mut().expect("the loop only carries on past a caller").caller;
+        depth += 1;
+    }
+    if let Some(mut module) = cursor.take() {
+        *cursor = module.caller.take();
+        return Some((module, Some(depth)));
+    }
+    
+    let module = vm.modules.borrow_mut().take(id)?;
+    Some((module, None))
+}
+fn put_back(vm: &mut RTRModule, mut module: Box<RTRModule>, depth: Option<usize>) {
+    let Some(depth) = depth else {
+        vm.modules.borrow_mut().put(module);
+        return;
+    };
+    
+    let mut cursor = &mut vm.caller;
+    for _ in 0..depth {
+        cursor = &mut cursor.as_mut().expect("the callers are as they were when it was taken").caller;
+    }
+    module.caller = cursor.take();
+    *cursor = Some(module);
+}
+
+// runs a module's top level in the module it was made for, giving back what it exports and the module
+pub fn evaluate(vm: &mut RTRModule, module: Box<RTRModule>, body: Rc<Chunk>, name: String) -> Result<(SharedValue, Box<RTRModule>), Error> {
+    let (out, module) = run_in(vm, module, |vm| {
+        let global = vm.scope.global;
+        match vm.run_module(body, global, Some(name), None) {
+            Ok(exports) => Ok(export(vm, exports)),
+            Err(err) => Err(Box::new(Failed::new(vm, err)))
+        }
+    });
+    
+    match out {
+        Ok(exports) => Ok((exports, module)),
+        Err(failed) => Err((*failed).into_error(&mut vm.memory))
+    }
+}
+
+// calls a function from another module, with the args and what it gives back copied between them.
+// arrays and objects passed in are brought up to date afterwards with whatever the function changed
+pub fn call(vm: &mut RTRModule, remote: &RemoteFunction, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    // methods get the stand in for the instance first, the call goes to the instance it stands in for
+    let sent = match &remote.receiver {
+        Some(_) => args.get(1..).unwrap_or_default(),
+        None => args
+    };
+    let sent: Vec<SharedValue> = sent
+        .iter()
+        .map(|ptr| export(vm, *ptr))
+        .collect();
+    
+    let Some((module, depth)) = find(vm, remote.func.module) else {
+        return Err(Error::CannotCall {
+            func: String::from("a function from a script that isnt running"),
+            range: None
+        });
+    };
+    
+    for ptr in args {
+        vm.memory.pin(*ptr);
+    }
+    let ((out, changed), module) = run_in(vm, module, |vm| {
+        let mut ptrs: Vec<MemPointer> = remote.receiver.iter().map(|receiver| receiver.ptr).collect();
+        ptrs.extend(sent.iter().map(|arg| arg.copy_in(&mut vm.memory)));
+        for ptr in &ptrs {
+            vm.memory.pin(*ptr);
+        }
+        
+        let out = vm.call_function(remote.func.ptr, &ptrs, None);
+        let out = match out {
+            Ok(ptr) => Ok(export(vm, ptr)),
+            Err(err) => Err(Failed::new(vm, err))
+        };
+        // the args as they are now, only arrays and objects can have been changed
+        let changed: Vec<Option<SharedValue>> = ptrs
+            .iter()
+            .map(|ptr| match vm.memory.get(*ptr) {
+                Value::Arr { .. } | Value::Obj { .. } => Some(export(vm, *ptr)),
+                _ => None
+            })
+            .collect();
+        
+        for ptr in &ptrs {
+            vm.memory.unpin(*ptr);
+        }
+        (out, changed)
+    });
+    put_back(vm, module, depth);
+    for ptr in args {
+        vm.memory.unpin(*ptr);
+    }
+    
+    for (ptr, value) in args.iter().zip(changed) {
+        if let Some(value) = value {
+            sync(&mut vm.memory, *ptr, &value);
+        }
+    }
+    
+    match out {
+        Ok(value) => Ok(value.copy_in(&mut vm.memory)),
+        Err(failed) => Err(failed.into_error(&mut vm.memory))
+    }
+}
@@ -1,61 +1,179 @@
 use std::collections::HashMap;
+use crate::rtr::runtime::instruction::Var;
 use crate::rtr::runtime::memory::{MemPointer, Memory};
+use crate::rtr::runtime::symbol::{Symbol, Symbols};
+use crate::rtr::runtime::value::Value;
 
+// scopes are environments living in memory, each pointing at the one it was made in,
+// so a function can hold onto the environment it was defined in after it has been left
 #[derive(Debug)]
 pub struct Scope {
-    pub layers: Vec<HashMap<String, MemPointer>>
+    pub env: MemPointer,
+    // where events run and top level variables live
+    pub global: MemPointer,
+    // the envs that were left to call a function or run an event, innermost last
+    pub callers: Vec<MemPointer>,
+    pub symbols: Symbols
+}
+
+fn vars_mut(memory: &mut Memory, env: MemPointer) -> &mut HashMap<Symbol, MemPointer> {
+    if let Value::Env { vars, .. } = memory.get_mut(env) {
+        vars
+    } else {
+        panic!("scope pointing at non-env value")
+    }
+}
+fn slot_mut(memory: &mut Memory, env: MemPointer, index: usize) -> &mut Option<MemPointer> {
+    if let Value::Env { slots, .. } = memory.get_mut(env) {
+        &mut slots[index].1
+    } else {
+        panic!("scope pointing at non-env value")
+    }
+}
+// where name is in an env, if it has been declared there
+fn find(env: &Value, name: Symbol) -> Option<MemPointer> {
+    let Value::Env { slots, vars, .. } = env else {
+        return None;
+    };
+    
+    slots
+        .iter()
+        .rfind(|(slot, _)| *slot == name)
+        .and_then(|(_, value)| *value)
+        .or_else(|| vars.get(&name).copied())
 }
 
 impl Scope {
-    pub fn new() -> Scope {
+    pub fn new(memory: &mut Memory) -> Scope {
+        let env = memory.alloc(Value::Env {
+            slots: Box::default(),
+            vars: HashMap::new(),
+            parent: None
+        });
+        
         Scope {
-            layers: vec![HashMap::new()]
+            env,
+            global: env,
+            callers: Vec::new(),
+            symbols: Symbols::default()
         }
     }
     
-    pub fn new_layer(&mut self) {
-        self.layers.push(HashMap::new());
+    // every env that is still in use, for the collector
+    pub fn roots(&self) -> impl Iterator<Item = MemPointer> + '_ {
+        [self.env, self.global]
+            .into_iter()
+            .chain(self.callers.iter().copied())
     }
-    pub fn pop_layer(&mut self) -> HashMap<String, MemPointer> {
-        self.layers.pop().unwrap()
+    
+    // names are the variables the compiler gave slots in it
+    pub fn new_layer(&mut self, memory: &mut Memory, names: &[Symbol]) {
+        self.env = memory.alloc(Value::Env {
+            slots: names.iter().map(|name| (*name, None)).collect(),
+            vars: HashMap::new(),
+            parent: Some(self.env)
+        });
     }
-    #[allow(clippy::unused_self)]
-    pub fn free_layer(&mut self, memory: &mut Memory, layer: &HashMap<String, MemPointer>) {
-        for var in layer.values() {
-            let ptr = *var;
-            
-            memory.rm_ref(ptr);
-            memory.free(ptr);
+    pub fn pop_layer(&mut self, memory: &Memory) {
+        let Value::Env { parent: Some(parent), .. } = memory.get(self.env) else {
+            panic!("popped the global scope");
+        };
+        self.env = *parent;
+    }
+    
+    // switches to another env, like a function's captured one, until leave is called
+    pub fn enter(&mut self, env: MemPointer) {
+        self.callers.push(self.env);
+        self.env = env;
+    }
+    pub fn leave(&mut self) {
+        self.env = self.callers.pop().expect("left more envs than were entered");
+    }
+    
+    // the env depth layers up from the current one
+    fn layer(&self, memory: &Memory, depth: usize) -> MemPointer {
+        let mut env = self.env;
+        for _ in 0..depth {
+            let Value::Env { parent: Some(parent), .. } = memory.get(env) else {
+                panic!("variable resolved past the global scope");
+            };
+            env = *parent;
         }
+        env
     }
     
-    pub fn decl_var(&mut self, memory: &mut Memory, name: String, value: MemPointer) {
-        let top = self.layers.last_mut().unwrap();
-        if let Some(existing) = top.get(name.as_str()) {
-            memory.rm_ref(*existing);
-            memory.free(*existing);
+    pub fn decl(&mut self, memory: &mut Memory, var: Var, value: MemPointer) {
+        match var {
+            Var::Slot { depth, index, .. } => {
+                let env = self.layer(memory, depth);
+                *slot_mut(memory, env, index) = Some(value);
+            }
+            Var::Name(name) => self.decl_var(memory, name, value)
         }
-        memory.add_ref(value);
-        top.insert(name, value);
-    }
-    pub fn set_var(&mut self, memory: &mut Memory, name: String, value: MemPointer) {
-        for layer in &mut self.layers {
-            if let Some(existing) = layer.get(name.as_str()) {
-                memory.rm_ref(*existing);
-                memory.free(*existing);
-                memory.add_ref(value);
-                layer.insert(name, value);
+    }
+    pub fn set(&mut self, memory: &mut Memory, var: Var, value: MemPointer) {
+        match var {
+            Var::Slot { depth, index, name } => {
+                let env = self.layer(memory, depth);
+                let slot = slot_mut(memory, env, index);
+                if slot.is_some() {
+                    *slot = Some(value);
+                } else {
+                    self.set_var(memory, name, value);
+                }
+            }
+            Var::Name(name) => self.set_var(memory, name, value)
+        }
+    }
+    pub fn get(&self, memory: &Memory, var: Var) -> Option<MemPointer> {
+        match var {
+            Var::Slot { depth, index, name } => {
+                let Value::Env { slots, .. } = memory.get(self.layer(memory, depth)) else {
+                    return None;
+                };
+                slots[index].1.or_else(|| self.get_var(memory, name))
+            }
+            Var::Name(name) => self.get_var(memory, name)
+        }
+    }
+    
+    pub fn decl_var(&mut self, memory: &mut Memory, name: Symbol, value: MemPointer) {
+        vars_mut(memory, self.env).insert(name, value);
+    }
+    pub fn set_var(&mut self, memory: &mut Memory, name: Symbol, value: MemPointer) {
+        let mut env = Some(self.env);
+        while let Some(ptr) = env {
+            let Value::Env { slots, vars, parent } = memory.get(ptr) else {
+                break;
+            };
+            
+            let parent = *parent;
+            if let Some(index) = slots.iter().rposition(|(slot, _)| *slot == name)
+                && slots[index].1.is_some() {
+                *slot_mut(memory, ptr, index) = Some(value);
                 return;
             }
+            if vars.contains_key(&name) {
+                vars_mut(memory, ptr).insert(name, value);
+                return;
+            }
+            env = parent;
         }
         self.decl_var(memory, name, value);
     }
-    pub fn get_var(&self, name: &str) -> Option<MemPointer> {
-        for scope in self.layers.iter().rev() {
-            if let Some(value) = scope.get(name) {
-                return Some(*value);
+    pub fn get_var(&self, memory: &Memory, name: Symbol) -> Option<MemPointer> {
+        let mut env = Some(self.env);
+        while let Some(ptr) = env {
+            let value = memory.get(ptr);
+            if let Some(found) = find(value, name) {
+                return Some(found);
             }
+            
+            let Value::Env { parent, .. } = value else {
+                break;
+            };
+            env = *parent;
         }
         None
     }
-}
\ No newline at end of file
+}
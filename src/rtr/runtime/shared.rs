@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use crate::rtr::runtime::memory::{MemPointer, Memory};
+use crate::rtr::runtime::remote::{self, Remote};
+use crate::rtr::runtime::value::{TypeValue, Value};
+use crate::shared::color::Color;
+
+// a value copied out of one module's memory so it can be put into another's.
+// only data makes the trip, functions and classes become null and instances become plain objects,
+// apart from what an imported file exports which can also hold handles back into it
+#[derive(Debug, Clone)]
+pub enum SharedValue {
+    Type(TypeValue),
+    
+    Null,
+    Str(String),
+    Num(f64),
+    Percentage(f64),
+    Bool(bool),
+    Color(Color),
+    Arr(Vec<SharedValue>),
+    Obj(HashMap<String, SharedValue>),
+    
+    Remote(Remote)
+}
+
+impl SharedValue {
+    pub fn copy_out(memory: &Memory, ptr: MemPointer) -> SharedValue {
+        copy_out(memory, ptr, &mut Vec::new())
+    }
+    pub fn copy_in(&self, memory: &mut Memory) -> MemPointer {
+        let value = match self {
+            SharedValue::Type(data) => Value::Type { data: data.clone() },
+            
+            SharedValue::Null => Value::Null,
+            SharedValue::Str(data) => Value::Str { data: data.clone() },
+            SharedValue::Num(data) => Value::Num { data: *data },
+            SharedValue::Percentage(data) => Value::Percentage { data: *data },
+            SharedValue::Bool(data) => Value::Bool { data: *data },
+            SharedValue::Color(data) => Value::Color { data: *data },
+            SharedValue::Arr(items) => Value::Arr {
+                items: items.iter().map(|item| item.copy_in(memory)).collect()
+            },
+            SharedValue::Obj(data) => Value::Obj {
+                data: data.iter().map(|(key, item)| (key.clone(), item.copy_in(memory))).collect(),
+                class: None
+            },
+            
+            // these may already be in memory
+            SharedValue::Remote(remote) => return remote::copy_in(remote, memory)
+        };
+        
+        memory.alloc(value)
+    }
+}
+
+// path is the arrays and objects being copied, something holding itself is cut off with null
+fn copy_out(memory: &Memory, ptr: MemPointer, path: &mut Vec<MemPointer>) -> SharedValue {
+    if path.contains(&ptr) {
+        return SharedValue::Null;
+    }
+    
+    match memory.get(ptr) {
+        Value::Type { data } => SharedValue::Type(data.clone()),
+        
+        Value::Str { data } => SharedValue::Str(data.clone()),
+        Value::Num { data } => SharedValue::Num(*data),
+        Value::Percentage { data } => SharedValue::Percentage(*data),
+        Value::Bool { data } => SharedValue::Bool(*data),
+        Value::Color { data } => SharedValue::Color(*data),
+        Value::Arr { items } => {
+            path.push(ptr);
+            let items = items.iter().map(|item| copy_out(memory, *item, path)).collect();
+            path.pop();
+            SharedValue::Arr(items)
+        }
+        Value::Obj { data, .. } => {
+            path.push(ptr);
+            let data = data.iter().map(|(key, item)| (key.clone(), copy_out(memory, *item, path))).collect();
+            path.pop();
+            SharedValue::Obj(data)
+        }
+        
+        Value::Null
+        | Value::Function(..)
+        | Value::Class { .. }
+        | Value::Env { .. } => SharedValue::Null
+    }
+}
+
+// a message from broadcast(name, value), ran as the event name in every script of the document
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    pub name: String,
+    pub value: SharedValue
+}
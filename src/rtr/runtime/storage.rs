@@ -0,0 +1,172 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::rtr::runtime::json;
+use crate::rtr::runtime::shared::SharedValue;
+
+// where storage keeps its data between runs, as the text json::write gave
+pub trait Store: Debug {
+    // None when nothing has been saved yet
+    fn read(&self) -> Result<Option<String>, String>;
+    fn write(&mut self, data: &str) -> Result<(), String>;
+}
+
+// a file for each document, kept in a data folder
+#[derive(Debug)]
+pub struct FileStore {
+    pub path: PathBuf
+}
+
+impl FileStore {
+    // the file for the document at doc, named after its full path so documents with the same name dont share
+    pub fn for_document(data_dir: &Path, doc: &Path) -> FileStore {
+        let doc = fs::canonicalize(doc).unwrap_or_else(|_| doc.to_path_buf());
+        let name: String = doc
+            .display()
+            .to_string()
+            .chars()
+            .map(|char| if char.is_ascii_alphanumeric() || char == '.' { char } else { '_' })
+            .collect();
+        
+        FileStore {
+            path: data_dir.join(format!("{}.json", name.trim_matches('_')))
+        }
+    }
+}
+
+impl Store for FileStore {
+    fn read(&self) -> Result<Option<String>, String> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        fs::read_to_string(&self.path)
+            .map(Some)
+            .map_err(|err| format!("{}: {err}", self.path.display()))
+    }
+    fn write(&mut self, data: &str) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("{}: {err}", dir.display()))?;
+        }
+        fs::write(&self.path, data)
+            .map_err(|err| format!("{}: {err}", self.path.display()))
+    }
+}
+
+// keeps the data in memory, for tests. clones share the same data, so a new module can be given
+// the store an old one wrote to, like a document being opened again
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    data: Rc<RefCell<Option<String>>>
+}
+
+impl Store for MemoryStore {
+    fn read(&self) -> Result<Option<String>, String> {
+        Ok(self.data.borrow().clone())
+    }
+    fn write(&mut self, data: &str) -> Result<(), String> {
+        *self.data.borrow_mut() = Some(String::from(data));
+        Ok(())
+    }
+}
+
+// how much a document can keep in storage. None leaves that limit off
+#[derive(Debug, Clone)]
+pub struct Quota {
+    pub keys: Option<usize>,
+    // the size of everything stored, as written to the store
+    pub bytes: Option<usize>
+}
+
+impl Default for Quota {
+    fn default() -> Quota {
+        Quota {
+            keys: Some(1024),
+            bytes: Some(1024 * 1024)
+        }
+    }
+}
+
+// the key value pairs behind the storage object, every change is written through to the store
+#[derive(Debug)]
+pub struct Storage {
+    store: Box<dyn Store>,
+    // read from the store the first time it is needed
+    data: Option<BTreeMap<String, SharedValue>>,
+    pub quota: Quota
+}
+
+impl Default for Storage {
+    fn default() -> Storage {
+        Storage::new(MemoryStore::default())
+    }
+}
+
+impl Storage {
+    pub fn new(store: impl Store + 'static) -> Storage {
+        Storage {
+            store: Box::new(store),
+            data: None,
+            quota: Quota::default()
+        }
+    }
+    
+    fn data(&mut self) -> Result<&mut BTreeMap<String, SharedValue>, String> {
+        if self.data.is_none() {
+            let data = match self.store.read()? {
+                Some(text) => match json::read(&text)? {
+                    SharedValue::Obj(data) => data.into_iter().collect(),
+                    _ => return Err(String::from("stored data isnt an object"))
+                },
+                None => BTreeMap::new()
+            };
+            self.data = Some(data);
+        }
+        
+        Ok(self.data.as_mut().unwrap())
+    }
+    fn save(&mut self, data: BTreeMap<String, SharedValue>) -> Result<(), String> {
+        if let Some(limit) = self.quota.keys
+            && data.len() > limit {
+                return Err(format!("more than {limit} keys"));
+            }
+        
+        let text = json::write(&SharedValue::Obj(data.clone().into_iter().collect()))?;
+        if let Some(limit) = self.quota.bytes
+            && text.len() > limit {
+                return Err(format!("more than {limit} bytes"));
+            }
+        
+        self.store.write(&text)?;
+        self.data = Some(data);
+        Ok(())
+    }
+    
+    pub fn get(&mut self, key: &str) -> Result<Option<SharedValue>, String> {
+        Ok(self.data()?.get(key).cloned())
+    }
+    pub fn keys(&mut self) -> Result<Vec<String>, String> {
+        Ok(self.data()?.keys().cloned().collect())
+    }
+    // nothing changes if the store cant take it
+    pub fn set(&mut self, key: &str, value: SharedValue) -> Result<(), String> {
+        let mut data = self.data()?.clone();
+        data.insert(String::from(key), value);
+        self.save(data)
+    }
+    // false if there was nothing stored under key
+    pub fn remove(&mut self, key: &str) -> Result<bool, String> {
+        let mut data = self.data()?.clone();
+        if data.remove(key).is_none() {
+            return Ok(false);
+        }
+        self.save(data)?;
+        Ok(true)
+    }
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.save(BTreeMap::new())
+    }
+}
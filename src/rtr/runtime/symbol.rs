@@ -0,0 +1,21 @@
+use std::collections::HashMap;
+
+// an interned variable name, so lookups dont have to hash strings
+pub type Symbol = usize;
+
+#[derive(Debug, Default)]
+pub struct Symbols {
+    ids: HashMap<String, Symbol>
+}
+
+impl Symbols {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        
+        let id = self.ids.len();
+        self.ids.insert(String::from(name), id);
+        id
+    }
+}
@@ -0,0 +1,139 @@
+use std::cell::Cell;
+use std::fmt::Debug;
+use std::rc::Rc;
+use std::time::Instant;
+use crate::rtr::runtime::memory::MemPointer;
+
+// where timers get the time from, in milliseconds
+pub trait Clock: Debug {
+    fn now(&self) -> f64;
+}
+
+// real time since it was made
+#[derive(Debug)]
+pub struct SystemClock {
+    start: Instant
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock {
+            start: Instant::now()
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        self.start.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+// only moves when advanced, so timers can be tested without waiting.
+// clones share the same time, so the host can keep one after handing it to a module
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    time: Rc<Cell<f64>>
+}
+
+impl ManualClock {
+    pub fn advance(&self, ms: f64) {
+        self.time.set(self.time.get() + ms);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        self.time.get()
+    }
+}
+
+#[derive(Debug)]
+struct Timer {
+    id: usize,
+    due: f64,
+    // how long until it runs again, None runs it once
+    interval: Option<f64>,
+    func: MemPointer
+}
+
+// the functions set to run later by after(ms, fn) and every(ms, fn), ran by the host each frame
+#[derive(Debug)]
+pub struct Timers {
+    clock: Box<dyn Clock>,
+    pending: Vec<Timer>,
+    next_id: usize
+}
+
+impl Default for Timers {
+    fn default() -> Timers {
+        Timers {
+            clock: Box::new(SystemClock::default()),
+            pending: Vec::new(),
+            next_id: 1
+        }
+    }
+}
+
+impl Timers {
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+    }
+    pub fn now(&self) -> f64 {
+        self.clock.now()
+    }
+    
+    pub fn add(&mut self, delay: f64, interval: Option<f64>, func: MemPointer) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        
+        self.pending.push(Timer {
+            id,
+            due: self.now() + delay.max(0.0),
+            interval: interval.map(|ms| ms.max(0.0)),
+            func
+        });
+        
+        id
+    }
+    // false if there was no timer with that id left
+    pub fn cancel(&mut self, id: usize) -> bool {
+        let len = self.pending.len();
+        self.pending.retain(|timer| timer.id != id);
+        self.pending.len() != len
+    }
+    
+    // the timers due by now, soonest first
+    pub fn due(&self, now: f64) -> Vec<usize> {
+        let mut due: Vec<&Timer> = self.pending
+            .iter()
+            .filter(|timer| timer.due <= now)
+            .collect();
+        due.sort_by(|a, b| a.due.total_cmp(&b.due).then(a.id.cmp(&b.id)));
+        
+        due.iter().map(|timer| timer.id).collect()
+    }
+    // takes a due timer out, or moves it on if it repeats, giving the function to call.
+    // a repeating timer that fell behind skips the runs it missed
+    pub fn fire(&mut self, id: usize, now: f64) -> Option<MemPointer> {
+        let i = self.pending.iter().position(|timer| timer.id == id)?;
+        let timer = &mut self.pending[i];
+        let func = timer.func;
+        
+        if let Some(interval) = timer.interval {
+            timer.due += interval;
+            if timer.due <= now {
+                timer.due = now + interval;
+            }
+        } else {
+            self.pending.remove(i);
+        }
+        
+        Some(func)
+    }
+    
+    // the functions waiting to run, for the collector
+    pub fn roots(&self) -> impl Iterator<Item = MemPointer> + '_ {
+        self.pending.iter().map(|timer| timer.func)
+    }
+}
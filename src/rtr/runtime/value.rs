@@ -1,12 +1,17 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 use crate::{print_raw, print_warn, Log, LogKind, print_log, print_error};
-use crate::rtr::ast::node::Parameter;
 use crate::rtr::error::Error;
-use crate::rtr::{IndexKey};
+use crate::rtr::{IndexKey, RTRModule, Wake};
 use crate::rtr::log::{RTRLog, RTRLogKind};
-use crate::rtr::runtime::instruction::VmInstruction;
+use crate::rtr::runtime::{callbacks, json, remote};
+use crate::rtr::runtime::remote::RemoteFunction;
+use crate::rtr::runtime::instruction::Chunk;
 use crate::rtr::runtime::memory::{MemPointer, Memory};
+use crate::rtr::runtime::shared::{Broadcast, SharedValue};
+use crate::rtr::runtime::symbol::Symbol;
+use crate::rtr::runtime::timers::Timers;
 use crate::shared::color::Color;
 use crate::shared::logging::LogSource;
 use crate::shared::utils::{chr, ord};
@@ -17,31 +22,58 @@ pub enum Value {
     
     Null,
     Str { data: String },
-    Num { data: f32 },
-    Percentage { data: f32 },
+    Num { data: f64 },
+    Percentage { data: f64 },
     Bool { data: bool },
     Function(Function),
     Arr {
         items: Vec<MemPointer>
     },
     Obj {
-        data: HashMap<String, MemPointer>
+        data: HashMap<String, MemPointer>,
+        // the class that made it, if any
+        class: Option<MemPointer>
     },
-    Color { data: Color }
+    Color { data: Color },
+    // made by a class statement, calling it makes an instance
+    Class {
+        name: String,
+        methods: HashMap<String, MemPointer>,
+        parent: Option<MemPointer>
+    },
+    
+    // a layer of variables, only made by the scope and held onto by functions
+    Env {
+        // the names the compiler gave slots, and what each holds once it is declared
+        slots: Box<[(Symbol, Option<MemPointer>)]>,
+        // the rest, like globals and names assigned without being declared
+        vars: HashMap<Symbol, MemPointer>,
+        parent: Option<MemPointer>
+    }
 }
 
 impl Value {
-    pub fn free(&self, memory: &mut Memory) {
+    // adds every pointer this value holds onto, so the collector keeps them alive
+    pub fn trace(&self, out: &mut Vec<MemPointer>) {
         match self {
             Value::Arr { items } => {
-                for item in items {
-                    memory.free(*item);
-                }
+                out.extend(items);
             }
-            Value::Obj { data } => {
-                for item in data.values() {
-                    memory.free(*item);
-                }
+            Value::Obj { data, class } => {
+                out.extend(data.values());
+                out.extend(class);
+            }
+            Value::Class { methods, parent, .. } => {
+                out.extend(methods.values());
+                out.extend(parent);
+            }
+            Value::Function(Function::Vm { env, .. }) => {
+                out.push(*env);
+            }
+            Value::Env { slots, vars, parent } => {
+                out.extend(slots.iter().filter_map(|(_, value)| *value));
+                out.extend(vars.values());
+                out.extend(parent);
             }
             
             _ => ()
@@ -59,30 +91,38 @@ impl Value {
                     items: new_items
                 }
             }
-            Value::Obj { data } => {
+            Value::Obj { data, class } => {
                 let mut new_map = HashMap::new();
                 for item in data {
                     let val = memory.get(*item.1).clone().dupe(memory);
                     new_map.insert(item.0.clone(), memory.alloc(val));
                 }
                 Value::Obj {
-                    data: new_map
+                    data: new_map,
+                    class: *class
                 }
             }
             _ => self.clone()
         }
     }
     
-    pub fn call(&self, logs: &mut Vec<RTRLog>, memory: &mut Memory, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    pub fn call(&self, vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
         match self {
             Value::Function(Function::Builtin(builtin)) => {
-                builtin.call(logs, memory, args)
+                builtin.call(vm, args)
             }
-            // Vm functions are handled in the call instruction
+            Value::Function(Function::Rust(func)) => {
+                func(vm, args)
+            }
+            Value::Function(Function::Remote(func)) => {
+                remote::call(vm, func, args)
+            }
+            // Vm functions are handled by RTRModule::call_function
             
             _ => {
                 Err(Error::CannotCall {
-                    func: self.stringify(memory)
+                    func: self.stringify(&vm.memory),
+                    range: None
                 })
             }
         }
@@ -113,8 +153,40 @@ impl Value {
                 TypeValue::Obj,
             Value::Color { .. } =>
                 TypeValue::Color {},
+            Value::Class { .. } =>
+                TypeValue::Class,
+            Value::Env { .. } =>
+                TypeValue::Env,
         }
     }
+    // the type scripts see, which for instances is the name of their class
+    pub fn type_of(&self, memory: &Memory) -> TypeValue {
+        if let Value::Obj { class: Some(class), .. } = self
+            && let Value::Class { name, .. } = memory.get(*class) {
+                return TypeValue::Instance { class: name.clone() };
+            }
+        
+        self.get_type()
+    }
+    // whether a match's `is name` fits, instances are objs and are every class they inherit from too
+    pub fn is_type(&self, memory: &Memory, name: &str) -> bool {
+        if self.get_type().to_string() == name {
+            return true;
+        }
+        
+        let mut class = if let Value::Obj { class, .. } = self { *class } else { None };
+        while let Some(ptr) = class {
+            let Value::Class { name: class_name, parent, .. } = memory.get(ptr) else {
+                break;
+            };
+            
+            if class_name == name {
+                return true;
+            }
+            class = *parent;
+        }
+        false
+    }
     
     pub fn stringify(&self, memory: &Memory) -> String {
         match self {
@@ -126,9 +198,9 @@ impl Value {
             Value::Str { data } =>
                 data.clone(),
             Value::Num { data } =>
-                data.to_string(),
+                format_num(*data),
             Value::Percentage { data } =>
-                format!("{data}%"),
+                format!("{}%", format_num(*data)),
             Value::Bool { data } =>
                 data.to_string(),
             Value::Arr { items } => {
@@ -141,7 +213,7 @@ impl Value {
                 }
                 format!("[{str}]")
             }
-            Value::Obj { data } => {
+            Value::Obj { data, class } => {
                 let mut str = String::new();
                 for (i, (key, ptr)) in data.iter().enumerate() {
                     str = format!("{str}{key}: {}", memory.get(*ptr).stringify_format(memory));
@@ -149,11 +221,19 @@ impl Value {
                         str = format!("{str}, ");
                     }
                 }
-                format!("{{{str}}}")
+                
+                if class.is_some() {
+                    format!("{} {{{str}}}", self.type_of(memory))
+                } else {
+                    format!("{{{str}}}")
+                }
             }
             Value::Color { data } => {
                 data.to_hex_rgb()
             }
+            Value::Class { name, .. } => {
+                format!("<class {name}>")
+            }
             
             _ => format!("<{}>", self.get_type())
         }
@@ -179,7 +259,7 @@ impl Value {
                 *data,
             Value::Arr { items } =>
                 !items.is_empty(),
-            Value::Obj { data } =>
+            Value::Obj { data, .. } =>
                 !data.is_empty(),
             Value::Color { data } =>
                 data.above_zero(),
@@ -187,10 +267,10 @@ impl Value {
             _ => true
         }
     }
-    pub fn numbify(&self) -> f32 {
+    pub fn numbify(&self) -> f64 {
         match self {
             Value::Str { data } =>
-                data.parse().unwrap_or(f32::NAN),
+                data.parse().unwrap_or(f64::NAN),
             Value::Num { data } =>
                 *data,
             Value::Percentage { data } =>
@@ -198,7 +278,7 @@ impl Value {
             Value::Bool { data } =>
                 if *data { 1.0 } else { 0.0 },
             
-             _ => f32::NAN
+             _ => f64::NAN
         }
     }
     pub fn arrify(&self, memory: &mut Memory) -> Vec<MemPointer> {
@@ -227,7 +307,7 @@ impl Value {
             Value::Arr { items } => {
                 items.clone()
             }
-            Value::Obj { data } => {
+            Value::Obj { data, .. } => {
                 data
                     .keys()
                     .map(|key| {
@@ -246,10 +326,10 @@ impl Value {
             Value::Str { data } =>
                 data.len(),
             Value::Num { data } | Value::Percentage { data } =>
-                data.to_string().len(),
+                format_num(*data).len(),
             Value::Arr { items } =>
                 items.len(),
-            Value::Obj { data } =>
+            Value::Obj { data, .. } =>
                 data.len(),
         
             _ => 0
@@ -266,13 +346,13 @@ impl Value {
             Value::Num { data } | Value::Percentage { data } =>
                 // TODO: handle out of range
                 memory.alloc(Value::Str {
-                    data: data.to_string()[key.numbify().trunc() as usize..].to_string()
+                    data: format_num(*data)[key.numbify().trunc() as usize..].to_string()
                 }),
             Value::Arr { items } =>
                 // TODO: handle out of range
                 *items.get(key.numbify().trunc() as usize)
                     .unwrap_or(&memory.alloc(Value::Null)),
-            Value::Obj { data } =>
+            Value::Obj { data, .. } =>
                 *data.get(&key.stringify(memory))
                     .unwrap_or(&memory.alloc(Value::Null)),
             
@@ -281,7 +361,7 @@ impl Value {
     }
     pub fn set_item(&mut self, key: IndexKey, value: MemPointer) -> Option<MemPointer> {
         match self {
-            Value::Obj { data } => {
+            Value::Obj { data, .. } => {
                 if let IndexKey::Str(key) = key {
                     data.insert(key, value)
                 } else {
@@ -297,11 +377,11 @@ impl Value {
             Value::Arr { items } => {
                 (0..items.len())
                     .map(|n| memory.alloc(Value::Num {
-                        data: n as f32
+                        data: n as f64
                     }))
                     .collect()
             }
-            Value::Obj { data } => {
+            Value::Obj { data, .. } => {
                 data.keys()
                     .map(|k| memory.alloc(Value::Str { data: k.clone() }))
                     .collect()
@@ -315,7 +395,7 @@ impl Value {
             Value::Arr { items } => {
                 items.clone()
             }
-            Value::Obj { data } => {
+            Value::Obj { data, .. } => {
                 data.values()
                     .copied()
                     .collect()
@@ -334,7 +414,7 @@ impl Value {
                 }
                 false
             }
-            Value::Obj { data } => {
+            Value::Obj { data, .. } => {
                 for item in data.keys() {
                     if key.equal(&Value::Str { data: item.clone() }) {
                         return true;
@@ -368,7 +448,28 @@ impl Value {
     }
 }
 
-impl From<Value> for f32 {
+// numbers are f64, so integers up to 2^53 (like millisecond timestamps) are exact.
+// they print the shortest way that reads back the same, with exponents once they get huge or tiny
+pub fn format_num(num: f64) -> String {
+    if num.is_nan() {
+        return String::from("NaN");
+    }
+    if num.is_infinite() {
+        return String::from(if num > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    if num == 0.0 {
+        // no -0
+        return String::from("0");
+    }
+    
+    if num.abs() >= 1e21 || num.abs() < 1e-6 {
+        format!("{num:e}")
+    } else {
+        num.to_string()
+    }
+}
+
+impl From<Value> for f64 {
     fn from(value: Value) -> Self {
         match value {
             Value::Str { data } =>
@@ -400,6 +501,22 @@ impl From<Value> for bool {
     }
 }
 
+// looks for a method on a class and then the classes it extends
+pub fn class_method(memory: &Memory, class: MemPointer, name: &str) -> Option<MemPointer> {
+    let mut class = Some(class);
+    while let Some(ptr) = class {
+        let Value::Class { methods, parent, .. } = memory.get(ptr) else {
+            break;
+        };
+        
+        if let Some(method) = methods.get(name) {
+            return Some(*method);
+        }
+        class = *parent;
+    }
+    None
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeValue {
     Type,
@@ -412,7 +529,13 @@ pub enum TypeValue {
     Function,
     Arr,
     Obj,
-    Color
+    Color,
+    Class,
+    // an object made by a class
+    Instance {
+        class: String
+    },
+    Env
 }
 
 impl Display for TypeValue {
@@ -429,27 +552,47 @@ impl Display for TypeValue {
             TypeValue::Arr => write!(f, "arr"),
             TypeValue::Obj => write!(f, "obj"),
             TypeValue::Color => write!(f, "color"),
+            TypeValue::Class => write!(f, "class"),
+            TypeValue::Instance { class } => write!(f, "{class}"),
+            TypeValue::Env => write!(f, "env"),
         }
     }
 }
 
+// builtins get the whole module so they can call back into script functions
+pub type RustFunction = fn(vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error>;
+
 #[derive(Debug, Clone)]
 pub enum Function {
     Builtin(BuiltinFunction),
-    Rust(fn(logs: &mut Vec<RTRLog>, memory: &mut Memory, args: &[MemPointer]) -> Result<MemPointer, Error>),
+    Rust(RustFunction),
     Vm {
-        body: Vec<VmInstruction>,
-        params: Vec<Parameter>
+        body: Rc<Chunk>,
+        // the slots of the env the body runs in, the first params of them are the params
+        names: Rc<[Symbol]>,
+        params: usize,
+        // taken from what it was first assigned to, for stack traces
+        name: Option<String>,
+        // the scope it was defined in, kept alive for as long as the function is
+        env: MemPointer
     },
+    // exported by a file that was imported, and ran in the module that file got
+    Remote(RemoteFunction)
 }
 
 #[derive(Debug, Clone)]
 pub enum BuiltinFunction {
     Log,
     Error,
-    Return,
     Typeof,
     Length,
+    Broadcast,
+    
+    // time
+    After,
+    Cancel,
+    Now,
+    Wait,
     
     // mathematical
     Min,
@@ -473,6 +616,10 @@ pub enum BuiltinFunction {
     ToLower,
     ToTitle,
     
+    // json
+    JsonParse,
+    JsonStringify,
+    
     // array
     Item,
     Range,
@@ -486,13 +633,26 @@ pub enum BuiltinFunction {
     // logical
     All,
     Any,
-    Not
+    Not,
+    
+    // callbacks
+    Map,
+    Filter,
+    Reduce,
+    Sort,
+    Find,
+    FindIndex,
+    ForEach,
+    Some,
+    Every
 }
 
 impl BuiltinFunction {
     #[allow(clippy::too_many_lines)]
     #[allow(clippy::unnecessary_wraps)]
-    pub fn call(&self, logs: &mut Vec<RTRLog>, memory: &mut Memory, args: &[MemPointer]) -> Result<MemPointer, Error> {
+    pub fn call(&self, vm: &mut RTRModule, args: &[MemPointer]) -> Result<MemPointer, Error> {
+        let RTRModule { logs, memory, broadcasts, timers, .. } = vm;
+        
         match self {
             BuiltinFunction::Log => {
                 let text = args
@@ -525,21 +685,54 @@ impl BuiltinFunction {
                     text
                 });
             },
-            BuiltinFunction::Return => {
-                // return is handled in call instruction
-            }
             BuiltinFunction::Typeof => {
                 return Ok(
                     memory.alloc(Value::Type {
-                        data: memory.get(args[0]).get_type()
+                        data: memory.get(args[0]).type_of(memory)
                     })
                 )
             }
+            BuiltinFunction::Broadcast => {
+                // copied now, so changing the value afterwards doesnt change what gets sent
+                let name = args.first().map(|ptr| memory.get(*ptr).stringify(memory)).unwrap_or_default();
+                let value = args.get(1).map_or(SharedValue::Null, |ptr| SharedValue::copy_out(memory, *ptr));
+                
+                broadcasts.push(Broadcast { name, value });
+            }
+            
+            // time
+            BuiltinFunction::After => {
+                return add_timer(memory, timers, args, false);
+            }
+            BuiltinFunction::Cancel => {
+                let Some(id) = args.first() else {
+                    return Err(Error::InvalidArgs {
+                        func: String::from("cancel"),
+                        wanted: String::from("(id)"),
+                        range: None
+                    });
+                };
+                let id = memory.get(*id).numbify();
+                let data = id >= 0.0 && timers.cancel(id as usize);
+                
+                return Ok(memory.alloc(Value::Bool { data }));
+            }
+            BuiltinFunction::Now => {
+                return Ok(memory.alloc(Value::Num { data: timers.now() }));
+            }
+            BuiltinFunction::Wait => {
+                // in seconds, unlike the timers
+                let secs = args.first().map_or(0.0, |ptr| memory.get(*ptr).numbify());
+                let at = timers.now() + secs * 1000.0;
+                
+                vm.suspend(Wake::At(at))?;
+                return Ok(vm.memory.alloc(Value::Null));
+            }
             BuiltinFunction::Length => {
                 // TODO: check amount of args
                 return Ok(
                     memory.alloc(Value::Num {
-                        data: memory.get(args[0]).length() as f32
+                        data: memory.get(args[0]).length() as f64
                     })
                 );
             }
@@ -712,6 +905,27 @@ impl BuiltinFunction {
                 )
             }
             
+            // json
+            BuiltinFunction::JsonParse => {
+                let text = args.first().map(|ptr| memory.get(*ptr).stringify(memory)).unwrap_or_default();
+                let value = json::parse(&text)
+                    .map_err(|reason| Error::InvalidJson { reason, range: None })?;
+                
+                return Ok(value.copy_in(memory));
+            }
+            BuiltinFunction::JsonStringify => {
+                // a number of spaces or the text itself, cut to 10 like js does
+                let indent = args.get(1).and_then(|ptr| match memory.get(*ptr) {
+                    Value::Num { data } => Some(" ".repeat(data.clamp(0.0, 10.0) as usize)),
+                    Value::Str { data } => Some(data.chars().take(10).collect()),
+                    _ => None
+                });
+                let value = args.first().map_or(Ok(String::from("null")), |ptr| json::stringify(memory, *ptr, indent))
+                    .map_err(|reason| Error::InvalidJson { reason, range: None })?;
+                
+                return Ok(memory.alloc(Value::Str { data: value }));
+            }
+            
             // array
             BuiltinFunction::Item => {
                 // TODO: handle incorrect amount of args
@@ -730,7 +944,7 @@ impl BuiltinFunction {
                 
                 for i in a..=b {
                     items.push(memory.alloc(
-                        Value::Num { data: i as f32 }
+                        Value::Num { data: i as f64 }
                     ));
                 }
                 
@@ -769,7 +983,8 @@ impl BuiltinFunction {
             }
             BuiltinFunction::Obj => {
                 return Ok(memory.alloc(Value::Obj {
-                    data: HashMap::new()
+                    data: HashMap::new(),
+                    class: None
                 }));
             }
             
@@ -794,8 +1009,47 @@ impl BuiltinFunction {
                     data: !memory.get(args[0]).boolify()
                 }))
             }
+            
+            // callbacks
+            BuiltinFunction::Map
+            | BuiltinFunction::Filter
+            | BuiltinFunction::Reduce
+            | BuiltinFunction::Sort
+            | BuiltinFunction::Find
+            | BuiltinFunction::FindIndex
+            | BuiltinFunction::ForEach
+            | BuiltinFunction::Some
+            | BuiltinFunction::Every => {
+                // every(ms, fn) is the repeating timer, every(items, fn) the callback
+                if let (BuiltinFunction::Every, Some(Value::Num { .. })) = (self, args.first().map(|ptr| memory.get(*ptr))) {
+                    return add_timer(memory, timers, args, true);
+                }
+                return callbacks::call(self, vm, args);
+            }
         }
         
         Ok(memory.alloc(Value::Null))
     }
 }
+
+// the function is checked now, so a mistake shows up where the timer was set rather than when it runs
+fn add_timer(memory: &mut Memory, timers: &mut Timers, args: &[MemPointer], repeat: bool) -> Result<MemPointer, Error> {
+    let (Some(ms), Some(func)) = (args.first(), args.get(1)) else {
+        return Err(timer_args(repeat));
+    };
+    if !matches!(memory.get(*func), Value::Function(..)) {
+        return Err(timer_args(repeat));
+    }
+    
+    let ms = memory.get(*ms).numbify();
+    let id = timers.add(ms, repeat.then_some(ms), *func);
+    
+    Ok(memory.alloc(Value::Num { data: id as f64 }))
+}
+fn timer_args(repeat: bool) -> Error {
+    Error::InvalidArgs {
+        func: String::from(if repeat { "every" } else { "after" }),
+        wanted: String::from("(ms, function)"),
+        range: None
+    }
+}
@@ -1,3 +1,4 @@
+use std::rc::Rc;
 use crate::shared::position::Position;
 use crate::shared::range::Range;
 use crate::shared::token::Token;
@@ -50,7 +51,7 @@ pub fn tokenise(text: &str) -> Vec<Token> {
         ln: 1,
         col: 1,
         i: 0,
-        script: text.to_string()
+        script: Rc::from(text)
     };
     
     let mut tokens: Vec<Token> = Vec::new();
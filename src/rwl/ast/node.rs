@@ -1,5 +1,6 @@
 use crate::rwl::value::PropertyPath;
 use crate::shared::color::Color;
+use crate::shared::range::Range;
 
 #[derive(Debug, Clone)]
 pub enum AstNode {
@@ -8,9 +9,10 @@ pub enum AstNode {
     Document(Vec<AstNode>),
     Block(BlockType, AstHeader, Vec<AstNode>),
     
-    Script(String, AstHeader),
+    // the range covers the raw script content
+    Script(String, AstHeader, Range),
     
-    Element(AstValue, AstHeader)
+    Element(AstValue, AstHeader, Range)
 }
 
 #[derive(Debug, Clone)]
@@ -38,6 +40,6 @@ pub struct AstHeader {
 }
 #[derive(Debug, Clone)]
 pub enum AstHeaderItem {
-    Pair(String, AstValue),
+    Pair(String, AstValue, Range),
     Flag(String)
 }
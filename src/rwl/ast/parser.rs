@@ -102,8 +102,10 @@ impl Parser {
     pub fn parse(&mut self) -> AstNodeOrErr {
         let mut statements: Vec<AstNode> = Vec::new();
         
+        self.consume_whitespace();
         while !self.at_end() {
             let statement = self.statement();
+            self.consume_whitespace();
             
             if statement.is_ok() {
                 statements.push(statement?);
@@ -150,7 +152,8 @@ impl Parser {
         
         Ok(statements)
     }
-    fn parse_text(&mut self) -> Result<String, Error> {
+    // returns the dedented text and the range of the raw text
+    fn parse_text(&mut self) -> Result<(String, Range), Error> {
         let mut text: String = String::new();
         
         self.consume_whitespace();
@@ -159,6 +162,9 @@ impl Parser {
         while self.peek() == TokenType::Space { self.consume(); }
         if self.peek() == TokenType::Newline { self.consume(); }
         
+        let start = self.peek().range.start;
+        let mut end = start.clone();
+        
         let mut quote_type: Option<QuoteType> = None;
         let mut depth: i32 = 1;
         
@@ -186,13 +192,13 @@ impl Parser {
                 depth -= 1;
             }
             
-            if self.peek() == TokenType::CloseCurly {
-                if depth == 0 {
-                    break;
-                }
+            if self.peek() == TokenType::CloseCurly && depth == 0 {
+                break;
             }
             
-            text = format!("{text}{}", self.consume());
+            let tkn = self.consume();
+            end = tkn.range.end.clone();
+            text = format!("{text}{tkn}");
         }
         
         text = remove_indent(&text);
@@ -200,7 +206,7 @@ impl Parser {
         self.consume_whitespace();
         self.expect(TokenType::CloseCurly)?;
         
-        Ok(text)
+        Ok((text, Range { start, end }))
     }
     
     fn statement(&mut self) -> AstNodeOrErr {
@@ -224,21 +230,24 @@ impl Parser {
         Ok(
             AstNode::Element(
                 value,
-                header
+                header,
+                Range {
+                    start,
+                    end: self.get_last_end()
+                }
             )
         )
     }
     fn block_statement(&mut self) -> AstNodeOrErr {
-        let start = self.get_next_start();
         let key = self.consume().to_string();
         
         // handle scripts
         if key.as_str() == "script" {
             let header = self.header()?;
             
-            let content = self.parse_text()?;
+            let (content, range) = self.parse_text()?;
             
-            return Ok(AstNode::Script(content, header));
+            return Ok(AstNode::Script(content, header, range));
         }
         
         self.consume_whitespace();
@@ -291,17 +300,25 @@ impl Parser {
     fn header_item(&mut self) -> Result<AstHeaderItem, Error> {
         self.consume_whitespace();
         
-        let tkn = self.consume().to_string();
+        let start = self.get_next_start();
+        let key = self.consume();
+        let tkn = key.to_string();
         
         self.consume_whitespace();
         if self.peek() == TokenType::Equal {
             if !is_alpha(tkn.as_str()) {
-                return Err(Error::InvalidAttributeKey);
+                return Err(Error::InvalidAttributeKey {
+                    key: tkn,
+                    range: Box::new(key.range)
+                });
             }
             self.consume();
             self.consume_whitespace();
             let value = self.value()?;
-            return Ok(AstHeaderItem::Pair(tkn, value))
+            return Ok(AstHeaderItem::Pair(tkn, value, Range {
+                start,
+                end: self.get_last_end()
+            }))
         }
         
         if is_alpha(tkn.as_str()) {
@@ -386,9 +403,12 @@ impl Parser {
             );
         }
         
-        let value: Result<i32, _> = self.consume().to_string().parse::<i32>();
+        let tkn = self.consume();
+        let value: Result<i32, _> = tkn.to_string().parse::<i32>();
         if let Err(_e) = value {
-            return Err(Error::CouldntParseNum);
+            return Err(Error::CouldntParseNum {
+                range: Box::new(tkn.range)
+            });
         }
         let value = value.unwrap();
         
@@ -400,17 +420,27 @@ impl Parser {
         }
     }
     fn color(&mut self) -> AstValueOrErr {
-        self.expect(TokenType::Hash)?;
-        let value = self.consume().to_string();
+        let start = self.expect(TokenType::Hash)?.range.start;
+        let tkn = self.consume();
+        let value = tkn.to_string();
+        let range = Range {
+            start,
+            end: tkn.range.end
+        };
         if ![3,6].contains(&value.len()) {
-            return Err(Error::InvalidHexLength);
+            return Err(Error::InvalidHexLength {
+                range: Box::new(range)
+            });
+        }
+        match parse_hex_color(&value) {
+            Ok(color) => Ok(AstValue::Color(color)),
+            Err(_) => Err(Error::InvalidHex {
+                range: Box::new(range)
+            })
         }
-        Ok(AstValue::Color(
-            // TODO: handle this better? :P
-            parse_hex_color(&value).unwrap()
-        ))
     }
     fn property(&mut self) -> AstValueOrErr {
+        let start = self.get_next_start();
         let source = self.expect_text()?;
         
         self.consume_whitespace();
@@ -418,6 +448,10 @@ impl Parser {
         self.consume_whitespace();
         
         let name = self.expect_text()?;
+        let range = Range {
+            start,
+            end: self.get_last_end()
+        };
         
         Ok(AstValue::Property(
             match source.as_str() {
@@ -433,7 +467,8 @@ impl Parser {
                         _ => {
                             return Err(Error::UnknownProperty {
                                 source,
-                                property: name
+                                property: name,
+                                range: Box::new(range)
                             });
                         }
                     })
@@ -441,7 +476,8 @@ impl Parser {
                 
                 _ => {
                     return Err(Error::UnknownPropertySource {
-                        source
+                        source,
+                        range: Box::new(range)
                     });
                 }
             }
@@ -9,6 +9,7 @@ use crate::shared::color::Color;
 use crate::shared::graphics::GLDrawHandle;
 use crate::shared::graphics_utils::Rounding;
 use crate::shared::logging::LogSource;
+use crate::shared::range::Range;
 use crate::shared::theme::Theme;
 use crate::shared::vec::Vec2;
 
@@ -88,17 +89,24 @@ impl From<AnchorX> for Alignment {
 #[derive(Debug, Clone)]
 pub struct Header {
     pub pairs: HashMap<String, Value>,
-    pub flags: Vec<String>
+    pub flags: Vec<String>,
+    // where each pair was written, for errors
+    pub ranges: HashMap<String, Range>
 }
 
 impl Header {
     pub fn new() -> Header {
         Header {
             pairs: HashMap::new(),
-            flags: Vec::new()
+            flags: Vec::new(),
+            ranges: HashMap::new()
         }
     }
     
+    pub fn range(&self, name: &str) -> Option<Box<Range>> {
+        self.ranges.get(name).cloned().map(Box::new)
+    }
+    
     pub fn get(&self, name: &str) -> Option<&Value> {
         if self.pairs.contains_key(&String::from(name)) {
             return self.pairs.get(&String::from(name));
@@ -110,10 +118,11 @@ impl Header {
         if self.pairs.contains_key(&String::from(name)) {
             let val = self.get(name).unwrap();
             if val.get_type() != type_name {
-                return Err(Error::ValueTypeMismatch(
-                    String::from(type_name),
-                    String::from(val.get_type())
-                ))
+                return Err(Error::ValueTypeMismatch {
+                    wanted: String::from(type_name),
+                    got: String::from(val.get_type()),
+                    range: self.range(name)
+                })
             }
             return Ok(Some(val))
         }
@@ -231,6 +240,7 @@ pub enum Node {
     Element {
         value: Value,
         header: Header,
+        range: Option<Range>,
         
         // TODO: replace with struct
         render_data: Option<(Area, String, f32, Color)>,
@@ -318,11 +328,13 @@ impl Node {
     
     pub fn new_element(
         value: Value,
-        header: Header
+        header: Header,
+        range: Option<Range>
     ) -> Node {
         Node::Element {
             value,
             header,
+            range,
             
             render_data: None,
         }
@@ -448,21 +460,21 @@ impl Node {
                 *render_data = Some(update_block(&update_ctx, &area, header)?);
             }
             
-            Node::Script { .. } => (),
-            
             Node::Element {
                 value,
                 header,
+                range,
                 
                 render_data,
             } => {
                 
-                let data = update_element(&update_ctx, parent_area, header, value, context)?;
+                let data = update_element(&update_ctx, parent_area, header, value, range.as_ref(), context)?;
                 
                 *render_data = Some(data);
             }
             
-            Node::Empty => ()
+            Node::Script { .. }
+            | Node::Empty => ()
         }
         
         Ok(())
@@ -528,21 +540,18 @@ fn update_frame(
                         Value::Percentage(val) =>
                             Ok(val / 100.0 * (total - used)),
                         
-                        Value::Str(..) =>
-                            Err(Error::ValueTypeMismatch (
-                                String::from("'num' or 'percentage'"),
-                                String::from("str")
-                            )),
-                        Value::Color(..) =>
-                            Err(Error::ValueTypeMismatch (
-                                String::from("'num' or 'percentage'"),
-                                String::from("color")
-                            )),
+                        Value::Str(..) | Value::Color(..) =>
+                            Err(Error::ValueTypeMismatch {
+                                wanted: String::from("'num' or 'percentage'"),
+                                got: String::from(pair_value.get_type()),
+                                range: header.range(pair_name)
+                            }),
                         Value::Property(..) =>
-                            Err(Error::ValueTypeMismatch (
-                                String::from("'num' or 'percentage'"),
-                                String::from("property")
-                            )),
+                            Err(Error::ValueTypeMismatch {
+                                wanted: String::from("'num' or 'percentage'"),
+                                got: String::from("property"),
+                                range: header.range(pair_name)
+                            }),
                     }?;
                 }
             }
@@ -596,6 +605,7 @@ fn update_element(
     area: &Area,
     header: &Header,
     value: &Value,
+    range: Option<&Range>,
     context: &mut ContainerContext
 ) -> Result<(Area, String, f32, Color), Error> {
     let text = match value {
@@ -603,7 +613,10 @@ fn update_element(
         Value::Num(num) => &*num.to_string(),
         
         _ => {
-            return Err(Error::InvalidElemType(String::from(value.get_type())))
+            return Err(Error::InvalidElemType {
+                type_name: String::from(value.get_type()),
+                range: range.cloned().map(Box::new)
+            })
         }
     };
     
@@ -748,7 +761,10 @@ fn get_anchor(header: &Header) -> Result<(Option<AnchorX>, Option<AnchorY>), Err
         if pair_name == "anchor"
             && let Value::Str(name) = pair_value {
             if !VALID_ANCHORS.contains(&name.as_str()) {
-                return Err(Error::InvalidAnchor(name.clone()))
+                return Err(Error::InvalidAnchor {
+                    got: name.clone(),
+                    range: header.range(pair_name)
+                })
             }
             
             if name.ends_with('l') || name.ends_with("left") {
@@ -785,7 +801,10 @@ fn get_alignment(header: &Header, default: Alignment) -> Result<Alignment, Error
         if pair_name == "alignment"
             && let Value::Str(name) = pair_value {
             if !VALID_ALIGNMENTS.contains(&name.as_str()) {
-                return Err(Error::InvalidAlignment(name.clone()))
+                return Err(Error::InvalidAlignment {
+                    got: name.clone(),
+                    range: header.range(pair_name)
+                })
             }
             
             if name.ends_with('l') || name.ends_with("left") {
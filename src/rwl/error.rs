@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use crate::shared::diagnostic::Diagnostic;
 use crate::shared::range::Range;
 use crate::shared::token::{Token, TokenType};
 use crate::rtr::error::Error as RTRError;
@@ -6,6 +7,7 @@ use crate::rtr::error::Error as RTRError;
 #[derive(Debug)]
 pub enum Error {
     Placeholder,
+    #[allow(clippy::upper_case_acronyms)]
     RTR(RTRError),
     
     // ast
@@ -22,22 +24,97 @@ pub enum Error {
         got: Box<Token>,
         range: Box<Range>
     },
-    CouldntParseNum,
-    InvalidAttributeKey,
-    InvalidHexLength,
+    CouldntParseNum {
+        range: Box<Range>
+    },
+    InvalidAttributeKey {
+        key: String,
+        range: Box<Range>
+    },
+    InvalidHexLength {
+        range: Box<Range>
+    },
+    InvalidHex {
+        range: Box<Range>
+    },
     UnknownPropertySource {
-        source: String
+        source: String,
+        range: Box<Range>
     },
     UnknownProperty {
         source: String,
-        property: String
+        property: String,
+        range: Box<Range>
     },
     
     // update
-    ValueTypeMismatch(String, String),
-    InvalidAnchor(String),
-    InvalidAlignment(String),
-    InvalidElemType(String)
+    ValueTypeMismatch {
+        wanted: String,
+        got: String,
+        range: Option<Box<Range>>
+    },
+    InvalidAnchor {
+        got: String,
+        range: Option<Box<Range>>
+    },
+    InvalidAlignment {
+        got: String,
+        range: Option<Box<Range>>
+    },
+    InvalidElemType {
+        type_name: String,
+        range: Option<Box<Range>>
+    }
+}
+
+impl Error {
+    pub fn range(&self) -> Option<&Range> {
+        match self {
+            Error::Placeholder =>
+                None,
+            Error::RTR(rtr) =>
+                rtr.range(),
+            
+            Error::UnexpectedToken { range, .. }
+            | Error::Expected { range, .. }
+            | Error::ExpectedText { range, .. }
+            | Error::CouldntParseNum { range }
+            | Error::InvalidAttributeKey { range, .. }
+            | Error::InvalidHexLength { range }
+            | Error::InvalidHex { range }
+            | Error::UnknownPropertySource { range, .. }
+            | Error::UnknownProperty { range, .. } =>
+                Some(range),
+            
+            Error::ValueTypeMismatch { range, .. }
+            | Error::InvalidAnchor { range, .. }
+            | Error::InvalidAlignment { range, .. }
+            | Error::InvalidElemType { range, .. } =>
+                range.as_deref()
+        }
+    }
+    
+    pub fn diagnostic(&self) -> Diagnostic {
+        if let Error::RTR(rtr) = self {
+            return rtr.diagnostic();
+        }
+        
+        let diagnostic = Diagnostic::new(self.to_string())
+            .with_range(self.range());
+        
+        match self {
+            Error::InvalidHexLength { .. } | Error::InvalidHex { .. } =>
+                diagnostic.with_help("colors are written as #rgb or #rrggbb"),
+            Error::UnknownPropertySource { .. } =>
+                diagnostic.with_note("the only property source is 'theme'"),
+            Error::UnknownProperty { .. } =>
+                diagnostic.with_note("theme properties are back, prim, seco, tert, text and accent"),
+            Error::InvalidElemType { .. } =>
+                diagnostic.with_note("elements can only be strings or numbers"),
+            
+            _ => diagnostic
+        }
+    }
 }
 
 impl Display for Error {
@@ -46,7 +123,7 @@ impl Display for Error {
             Error::Placeholder =>
                 write!(f, "placeholder err :P"),
             Error::RTR(rtr) =>
-                write!(f, "{}", rtr),
+                write!(f, "{rtr}"),
             
             // ast
             Error::UnexpectedToken { token, .. } =>
@@ -62,25 +139,27 @@ impl Display for Error {
                 ),
             Error::ExpectedText { got, ..} =>
                 write!(f, "expected text, got {got}"),
-            Error::CouldntParseNum =>
+            Error::CouldntParseNum { .. } =>
                 write!(f, "couldnt parse num"),
-            Error::InvalidAttributeKey =>
-                write!(f, "attribute key must consist of letters or _"),
-            Error::InvalidHexLength =>
+            Error::InvalidAttributeKey { key, .. } =>
+                write!(f, "attribute key '{key}' must consist of letters or _"),
+            Error::InvalidHexLength { .. } =>
                 write!(f, "hex values can only be 3 or 6 characters long"),
-            Error::UnknownPropertySource { source } =>
+            Error::InvalidHex { .. } =>
+                write!(f, "invalid hex color"),
+            Error::UnknownPropertySource { source, .. } =>
                 write!(f, "unknown property source '{source}'"),
-            Error::UnknownProperty { source, property } =>
+            Error::UnknownProperty { source, property, .. } =>
                 write!(f, "unknown property '{property}' on {source}"),
             
             // update
-            Error::ValueTypeMismatch(wanted, got) =>
+            Error::ValueTypeMismatch { wanted, got, .. } =>
                 write!(f, "expected {wanted}, got {got}"),
-            Error::InvalidAnchor(got) =>
+            Error::InvalidAnchor { got, .. } =>
                 write!(f, "invalid anchor '{got}'"), // add list of anchors?
-            Error::InvalidAlignment(got) =>
+            Error::InvalidAlignment { got, .. } =>
                 write!(f, "invalid alignment '{got}'"),  // add list of alignments?
-            Error::InvalidElemType(type_name) =>
+            Error::InvalidElemType { type_name, .. } =>
                 write!(f, "cannot have {type_name} as element")
         }
     }
@@ -1,5 +1,11 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::{print_raw, print_warn, Log, LogKind, print_log};
 use crate::rtr::RTRModule;
+use crate::rtr::modules::ModuleCache;
+use crate::rtr::runtime::storage::Storage;
 use crate::rwl::ast::node::{AstHeader, AstHeaderItem, AstNode, AstValue};
 use crate::rwl::ast::parser::Parser;
 use crate::rwl::ast::tokenise;
@@ -8,7 +14,10 @@ use crate::rwl::error::Error;
 use crate::rwl::script::RWLScript;
 use crate::rwl::value::Value;
 use crate::shared::area::Area;
+use crate::shared::diagnostic::Diagnostic;
 use crate::shared::graphics::GLDrawHandle;
+use crate::shared::logging::LogSource;
+use crate::shared::range::Range;
 
 pub mod ast;
 pub mod element;
@@ -16,11 +25,20 @@ pub mod error;
 pub mod value;
 mod script;
 
+// how many times broadcasts can set off more broadcasts before the rest are dropped
+const MAX_BROADCAST_ROUNDS: usize = 64;
+
 #[derive(Debug)]
 pub struct RWLInstance {
     pub ast: AstNode,
     pub root: NodeWrapper,
-    pub scripts: Vec<RWLScript>
+    pub scripts: Vec<RWLScript>,
+    // where the document came from, used when reporting errors and to find imports
+    pub path: Option<PathBuf>,
+    // files imported by any of the scripts, so each is only parsed and ran once
+    pub modules: Rc<RefCell<ModuleCache>>,
+    // what the scripts keep between runs, in memory unless the host gives it a store
+    pub storage: Rc<RefCell<Storage>>
 }
 
 impl RWLInstance {
@@ -28,33 +46,78 @@ impl RWLInstance {
         RWLInstance {
             ast: AstNode::Empty,
             root: NodeWrapper::new(Node::Document { children: Vec::new() }),
-            scripts: Vec::new()
+            scripts: Vec::new(),
+            path: None,
+            modules: Rc::default(),
+            storage: Rc::default()
         }
     }
     
-    pub fn parse(&mut self, src: &str) {
+    pub fn parse(&mut self, src: &str) -> Result<(), Error> {
         let mut parser = Parser {
             pointer: 0,
             tokens: tokenise(src)
         };
         
-        let out = parser.parse();
+        self.ast = parser.parse()?;
         
-        match out {
-            Err(err) => {
-                eprintln!("{err}");
-            }
-            Ok(ast) => {
-                self.ast = ast;
-            }
-        }
+        Ok(())
+    }
+    
+    pub fn diagnostic(&self, err: &Error) -> Diagnostic {
+        err.diagnostic()
+            .or_file(self.path.as_ref().map(|path| path.display()))
     }
     
     pub fn instance(&mut self) -> Result<(), Error> {
         self.root = self.instance_node(&self.ast.clone())?;
         
+        // the code outside of events is part of loading, so a script whose imports are broken
+        // fails the document like a parse error would
+        for script in &mut self.scripts {
+            script.module.load().map_err(Error::RTR)?;
+        }
+        // every script exists before any onload runs, so broadcasts from onload reach all of them
+        for script in &mut self.scripts {
+            script.init();
+        }
+        self.deliver_broadcasts();
+        
         Ok(())
     }
+    // called by the host once per frame, delta being the ms since the last one
+    pub fn frame(&mut self, delta: f64) {
+        for script in &mut self.scripts {
+            script.frame(delta);
+        }
+        self.deliver_broadcasts();
+    }
+    // hands every script's broadcasts to every script, including the one that sent it
+    pub fn deliver_broadcasts(&mut self) {
+        for _ in 0..MAX_BROADCAST_ROUNDS {
+            let broadcasts: Vec<_> = self.scripts
+                .iter_mut()
+                .flat_map(|script| script.module.take_broadcasts())
+                .collect();
+            if broadcasts.is_empty() {
+                return;
+            }
+            
+            for broadcast in &broadcasts {
+                for script in &mut self.scripts {
+                    script.receive(broadcast);
+                }
+            }
+        }
+        
+        let dropped: usize = self.scripts
+            .iter_mut()
+            .map(|script| script.module.take_broadcasts().len())
+            .sum();
+        if dropped > 0 {
+            print_warn!(LogSource::Rwl, "dropped {dropped} broadcasts, they kept sending more after {MAX_BROADCAST_ROUNDS} rounds");
+        }
+    }
     fn instance_nodes(&mut self, nodes: &[AstNode]) -> Result<Vec<NodeWrapper>, Error> {
         nodes
             .iter()
@@ -80,8 +143,8 @@ impl RWLInstance {
                 )
             ),
             
-            AstNode::Script(content, header) => {
-                self.instance_script(content.clone())?;
+            AstNode::Script(content, header, range) => {
+                self.instance_script(range)?;
                 
                 NodeWrapper::new(
                     Node::new_script(
@@ -91,18 +154,29 @@ impl RWLInstance {
                 )
             },
             
-            AstNode::Element(value, header) => NodeWrapper::new(
+            AstNode::Element(value, header, range) => NodeWrapper::new(
                 Node::new_element(
                     Self::instance_value(value),
-                    Self::instance_header(header)
+                    Self::instance_header(header),
+                    Some(range.clone())
                 )
             ),
         })
     }
-    fn instance_script(&mut self, content: String) -> Result<(), Error> {
+    fn instance_script(&mut self, range: &Range) -> Result<(), Error> {
         let mut module = RTRModule::new();
+        module.path.clone_from(&self.path);
+        module.modules = Rc::clone(&self.modules);
+        module.storage = Rc::clone(&self.storage);
         
-        let out =module.parse(&content);
+        // parse the raw text rather than the dedented one, so errors point into the document
+        let raw: String = range.start.script
+            .chars()
+            .skip(range.start.i)
+            .take(range.end.i - range.start.i)
+            .collect();
+        
+        let out = module.parse_at(&raw, range.start.clone());
         if let Err(err) = out {
             return Err(Error::RTR(err));
         }
@@ -111,21 +185,21 @@ impl RWLInstance {
         
         self.scripts.push(script);
         
-        self.scripts.last_mut().unwrap().init();
-        
         Ok(())
     }
     fn instance_header(header: &AstHeader) -> Header {
         let mut pairs: HashMap<String, Value> = HashMap::new();
         let mut flags: Vec<String> = Vec::new();
+        let mut ranges: HashMap<String, Range> = HashMap::new();
         
         for attr in &header.attributes[0..] {
             match attr {
-                AstHeaderItem::Pair(name, value) => {
+                AstHeaderItem::Pair(name, value, range) => {
                     pairs.insert(
                         name.clone(),
                         Self::instance_value(value)
                     );
+                    ranges.insert(name.clone(), range.clone());
                 }
                 AstHeaderItem::Flag(name) => {
                     flags.push(name.clone());
@@ -135,7 +209,8 @@ impl RWLInstance {
         
         Header {
             pairs,
-            flags
+            flags,
+            ranges
         }
     }
     fn instance_value(value: &AstValue) -> Value {
@@ -160,7 +235,7 @@ impl RWLInstance {
         let out = self.root.update(update_ctx, area, &mut ContainerContext::new());
         
         if let Err(err) = out {
-            eprintln!("{err}");
+            self.diagnostic(&err).emit();
         }
     }
 }
@@ -1,7 +1,8 @@
-use crate::{print_raw, print_warn, Log, LogKind, print_log, print_error};
 use crate::rtr::ast::node::EventTarget;
 use crate::rtr::RTRModule;
-use crate::shared::logging::LogSource;
+use crate::rtr::error::Error;
+use crate::rtr::runtime::shared::{Broadcast, SharedValue};
+use crate::rtr::runtime::value::Value;
 
 #[derive(Debug)]
 pub struct RWLScript {
@@ -22,11 +23,39 @@ impl RWLScript {
         });
     }
     
-    pub fn run_event_target(&mut self, target: &EventTarget) {
-        let out = self.module.run_event_target(target);
+    // what the event returned, errors are reported here and give nothing
+    pub fn run_event_target(&mut self, target: &EventTarget) -> Option<Value> {
+        self.run_event_target_args(target, &[])
+    }
+    pub fn run_event_target_args(&mut self, target: &EventTarget, args: &[SharedValue]) -> Option<Value> {
+        let out = self.module.run_event_target_args(target, args);
+        self.report(out)
+    }
+    // runs the event named by the broadcast with its value
+    pub fn receive(&mut self, broadcast: &Broadcast) {
+        self.run_event_target_args(&EventTarget::Global {
+            name: broadcast.name.clone()
+        }, std::slice::from_ref(&broadcast.value));
+    }
+    // runs the timers that are due and carries on with events done waiting, then runs onframe with the ms since the last frame
+    pub fn frame(&mut self, delta: f64) {
+        let out = self.module.run_timers();
+        self.report(out.map(|()| None));
+        let out = self.module.resume();
+        self.report(out.map(|()| None));
         
-        if let Err(err) = out {
-            print_error!(LogSource::Rtr, "{}", err);
+        self.run_event_target_args(&EventTarget::Global {
+            name: String::from("onframe")
+        }, &[SharedValue::Num(delta)]);
+    }
+    
+    fn report(&self, out: Result<Option<Value>, Error>) -> Option<Value> {
+        match out {
+            Ok(out) => out,
+            Err(err) => {
+                self.module.diagnostic(&err).emit();
+                None
+            }
         }
     }
 }
@@ -0,0 +1,120 @@
+use std::fmt::{Display, Formatter};
+use std::io::{stderr, IsTerminal};
+use colored::{ColoredString, Colorize};
+use crate::shared::range::Range;
+
+// an error ready to be shown to a person, with the source it points at
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub range: Option<Range>,
+    pub file: Option<String>,
+    pub notes: Vec<String>,
+    pub help: Option<String>
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            message: message.into(),
+            range: None,
+            file: None,
+            notes: Vec::new(),
+            help: None
+        }
+    }
+    
+    pub fn with_range(mut self, range: Option<&Range>) -> Diagnostic {
+        self.range = range.cloned();
+        self
+    }
+    pub fn with_file(mut self, file: Option<impl Display>) -> Diagnostic {
+        self.file = file.map(|f| f.to_string());
+        self
+    }
+    // keeps the file the diagnostic already points into, like one from an imported file
+    pub fn or_file(self, file: Option<impl Display>) -> Diagnostic {
+        if self.file.is_some() {
+            return self;
+        }
+        self.with_file(file)
+    }
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help = Some(help.into());
+        self
+    }
+    
+    // prints to stderr, only using colors when it is a terminal
+    pub fn emit(&self) {
+        eprintln!("{}", self.render(stderr().is_terminal()));
+    }
+    
+    pub fn render(&self, color: bool) -> String {
+        let paint = |text: &str, style: fn(&str) -> ColoredString| {
+            if color { style(text).to_string() } else { text.to_string() }
+        };
+        
+        let mut lines = vec![format!(
+            "{}{}",
+            paint("error", |t| t.bright_red().bold()),
+            paint(&format!(": {}", self.message), |t| t.bold())
+        )];
+        
+        let gutter = self.range
+            .as_ref()
+            .map_or(1, |range| range.start.ln.to_string().len());
+        let pad = " ".repeat(gutter);
+        let bar = paint("|", |t| t.bright_blue().bold());
+        
+        let location = match (&self.file, &self.range) {
+            (Some(file), Some(range)) => Some(format!("{file}:{}", range.start)),
+            (Some(file), None) => Some(file.clone()),
+            (None, Some(range)) => Some(range.start.to_string()),
+            (None, None) => None
+        };
+        if let Some(location) = location {
+            lines.push(format!("{pad}{} {location}", paint("-->", |t| t.bright_blue().bold())));
+        }
+        
+        if let Some(range) = &self.range
+            && let Some(source) = range.start.script.lines().nth(range.start.ln.saturating_sub(1)) {
+                let start_col = range.start.col.max(1);
+                let end_col = if range.end.ln == range.start.ln {
+                    range.end.col
+                } else {
+                    source.chars().count() + 1
+                };
+                
+                // keep tabs so the carets line up with the source
+                let offset: String = source
+                    .chars()
+                    .take(start_col - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let carets = "^".repeat(end_col.saturating_sub(start_col).max(1));
+                
+                lines.push(format!("{pad} {bar}"));
+                lines.push(format!("{} {bar} {source}", paint(&range.start.ln.to_string(), |t| t.bright_blue().bold())));
+                lines.push(format!("{pad} {bar} {offset}{}", paint(&carets, |t| t.bright_red().bold())));
+            }
+        
+        for note in &self.notes {
+            lines.push(format!("{pad} {} {note}", paint("= note:", |t| t.bold())));
+        }
+        if let Some(help) = &self.help {
+            lines.push(format!("{pad} {} {help}", paint("= help:", |t| t.bright_cyan().bold())));
+        }
+        
+        lines.join("\n")
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
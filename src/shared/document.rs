@@ -1,19 +1,158 @@
+use std::cell::RefCell;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::rtr::runtime::storage::{FileStore, Storage};
 use crate::rwl::RWLInstance;
+use crate::shared::area::Area;
+use crate::shared::color::Color;
+use crate::shared::diagnostic::Diagnostic;
+use crate::shared::fs::read_file;
 use crate::shared::graphics::GLDrawHandle;
+use crate::shared::graphics_utils::Rounding;
+use crate::shared::vec::Vec2;
+use crate::shared::watcher::FileWatcher;
 
 #[derive(Debug)]
 pub struct Document {
-    pub rwl_instance: RWLInstance
+    pub rwl_instance: RWLInstance,
+    pub path: Option<PathBuf>,
+    
+    // shown on top of the document instead of tearing the app down
+    pub error: Option<Diagnostic>,
+    watcher: Option<FileWatcher>,
+    // what the last load read besides the document, even if it failed
+    imports: Vec<PathBuf>
 }
 
 impl Document {
     pub fn new() -> Document {
         Document {
-            rwl_instance: RWLInstance::new()
+            rwl_instance: RWLInstance::new(),
+            path: None,
+            
+            error: None,
+            watcher: None,
+            imports: Vec::new()
+        }
+    }
+    pub fn open(path: PathBuf) -> Document {
+        let mut doc = Document::new();
+        doc.path = Some(path);
+        doc.reload();
+        doc
+    }
+    
+    // re-parses and re-instances the document, keeping the old one if that fails
+    pub fn reload(&mut self) {
+        let Some(path) = &self.path else { return };
+        
+        let mut instance = new_instance(path);
+        let loaded = load_instance(&mut instance, path);
+        // taken before a failed instance is dropped, so a broken or newly added import is watched too
+        self.imports = instance.modules.borrow().paths().cloned().collect();
+        
+        match loaded {
+            Ok(()) => {
+                self.rwl_instance = instance;
+                self.error = None;
+            }
+            Err(err) => {
+                self.error = Some(*err);
+            }
+        }
+        
+        let files = self.watched_files();
+        if let Some(watcher) = &mut self.watcher {
+            watcher.watch(files);
+        }
+    }
+    
+    pub fn watch(&mut self) {
+        let mut watcher = FileWatcher::new();
+        watcher.watch(self.watched_files());
+        self.watcher = Some(watcher);
+    }
+    // the document and everything its scripts tried to import the last time it was loaded
+    fn watched_files(&self) -> Vec<PathBuf> {
+        self.path
+            .iter()
+            .chain(&self.imports)
+            .cloned()
+            .collect()
+    }
+    
+    // returns true when the document was reloaded and needs a new layout
+    pub fn poll(&mut self) -> bool {
+        let changed = self.watcher
+            .as_mut()
+            .is_some_and(FileWatcher::changed);
+        
+        if changed {
+            self.reload();
         }
+        
+        changed
+    }
+    
+    // lets the scripts run their timers and onframe
+    pub fn frame(&mut self, delta: f64) {
+        self.rwl_instance.frame(delta);
     }
     
-    pub fn render(&mut self, d: &mut GLDrawHandle) {
+    pub fn render(&mut self, d: &mut GLDrawHandle, area: &Area) {
         self.rwl_instance.render(d);
+        
+        if let Some(err) = &self.error {
+            render_error(d, area, err);
+        }
+    }
+}
+
+fn new_instance(path: &Path) -> RWLInstance {
+    let mut instance = RWLInstance::new();
+    instance.path = Some(path.to_path_buf());
+    instance.storage = Rc::new(RefCell::new(Storage::new(
+        FileStore::for_document(&data_dir(), path)
+    )));
+    instance
+}
+fn load_instance(instance: &mut RWLInstance, path: &PathBuf) -> Result<(), Box<Diagnostic>> {
+    let src = read_file(path)
+        .map_err(|err| Box::new(Diagnostic::new(err).with_file(Some(path.display()))))?;
+    
+    instance.parse(&src)
+        .map_err(|err| Box::new(instance.diagnostic(&err)))?;
+    instance.instance()
+        .map_err(|err| Box::new(instance.diagnostic(&err)))?;
+    
+    Ok(())
+}
+
+// where documents keep their storage, DATA_DIR=path to change it
+fn data_dir() -> PathBuf {
+    env::var("DATA_DIR")
+        .map_or_else(|_| PathBuf::from("./data"), PathBuf::from)
+}
+
+fn render_error(d: &mut GLDrawHandle, area: &Area, err: &Diagnostic) {
+    let err = err.render(false);
+    let size = 16.0;
+    let padding = 10.0;
+    let lines = err.lines().count().max(1) as f32;
+    
+    let overlay = Area {
+        a: area.a,
+        b: Vec2(area.b.0, area.a.1 + padding * 2.0 + lines * size * 1.25)
+    };
+    d.draw_filled_rectangle(&overlay, &Rounding::default(), Color { r: 40, g: 0, b: 0, a: 230 });
+    
+    for (i, line) in err.lines().enumerate() {
+        d.draw_text(
+            line,
+            area.a + Vec2(padding, padding + i as f32 * size * 1.25),
+            size,
+            Color { r: 255, g: 120, b: 120, a: 255 }
+        );
     }
 }
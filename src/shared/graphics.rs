@@ -23,6 +23,12 @@ impl GLCtx {
         next_frame().await;
     }
     
+    // how long the last frame took, in ms
+    #[allow(clippy::unused_self)]
+    pub fn frame_time(&self) -> f64 {
+        f64::from(get_frame_time()) * 1000.0
+    }
+    
     #[allow(clippy::unused_self)]
     pub fn running(&self) -> bool {
         self.running
@@ -12,3 +12,5 @@ pub mod token;
 pub mod position;
 pub mod range;
 pub mod logging;
+pub mod diagnostic;
+pub mod watcher;
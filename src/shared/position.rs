@@ -1,12 +1,14 @@
 use std::fmt::{Display, Formatter};
 use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
 pub struct Position {
     pub ln: usize,
     pub col: usize,
     pub i: usize,
-    pub script: String
+    // shared between every position in a source, so ranges are cheap to keep around
+    pub script: Rc<str>
 }
 
 impl Display for Position {
@@ -80,7 +80,7 @@ pub enum TokenType {
     Comma, SemiColon, Colon, Period, Hash, Tilde, Dollar,
     Plus, Minus, Star, Slash, Mod, Pow,
     ExclamationMark, QuestionMark,
-    BackSlash, Pipe, Equal,
+    BackSlash, Pipe, Ampersand, Equal,
     
     Quote, DoubleQuote, BackQuote,
     
@@ -91,7 +91,7 @@ pub enum TokenType {
     Text(String)
 }
 
-pub const TOKENS: [char; 31] = [
+pub const TOKENS: [char; 32] = [
     '(', ')',
     '[', ']',
     '{', '}',
@@ -101,7 +101,7 @@ pub const TOKENS: [char; 31] = [
     ',',';',':','.','#','~','$',
     '+','-','*','/','%','^',
     '!', '?',
-    '\\', '|', '=',
+    '\\', '|', '&', '=',
     
     '\'','"','`',
     
@@ -120,7 +120,7 @@ impl From<char> for TokenType {
             ',' => TokenType::Comma, ';' => TokenType::SemiColon, ':' => TokenType::Colon, '.' => TokenType::Period, '#' => TokenType::Hash, '~' => TokenType::Tilde, '$' => TokenType::Dollar,
             '+' => TokenType::Plus, '-' => TokenType::Minus, '*' => TokenType::Star, '/' => TokenType::Slash, '%' => TokenType::Mod, '^' => TokenType::Pow,
             '!' => TokenType::ExclamationMark, '?' => TokenType::QuestionMark,
-            '\\' => TokenType::BackSlash, '|' => TokenType::Pipe, '=' => TokenType::Equal,
+            '\\' => TokenType::BackSlash, '|' => TokenType::Pipe, '&' => TokenType::Ampersand, '=' => TokenType::Equal,
             
             '\'' => TokenType::Quote, '"' => TokenType::DoubleQuote, '`' => TokenType::BackQuote,
             
@@ -168,7 +168,7 @@ impl Display for TokenType {
             TokenType::Comma => ",", TokenType::SemiColon => ";", TokenType::Colon => ":", TokenType::Period => ".", TokenType::Hash => "#", TokenType::Tilde => "~", TokenType::Dollar => "$",
             TokenType::Plus => "+", TokenType::Minus => "-", TokenType::Star => "*", TokenType::Slash => "/", TokenType::Mod => "%", TokenType::Pow => "^",
             TokenType::ExclamationMark => "!", TokenType::QuestionMark => "?",
-            TokenType::BackSlash => "\\", TokenType::Pipe => "|", TokenType::Equal => "=",
+            TokenType::BackSlash => "\\", TokenType::Pipe => "|", TokenType::Ampersand => "&", TokenType::Equal => "=",
             
             TokenType::Quote => "'", TokenType::DoubleQuote => "\"", TokenType::BackQuote => "`",
             
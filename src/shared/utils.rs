@@ -6,17 +6,17 @@ pub fn is_numeric(s: &str) -> bool {
 }
 
 // cursed things to match js
-pub fn chr(num: f32) -> String {
+pub fn chr(num: f64) -> String {
     let n = num.trunc() as i64;
     let code_unit = i128::from(n).rem_euclid(65536) as u16;
     String::from_utf16(&[code_unit]).unwrap()
 }
 
-pub fn ord(str: &str) -> f32 {
+pub fn ord(str: &str) -> f64 {
     if str.is_empty() {
-        f32::NAN
+        f64::NAN
     } else {
-        str.chars().next().unwrap() as u32 as f32
+        f64::from(str.chars().next().unwrap() as u32)
     }
 }
 
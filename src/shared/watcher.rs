@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// polls modification times instead of pulling in a platform watcher,
+// checking a handful of files a few times a second is plenty for an edit loop
+#[derive(Debug)]
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant
+}
+
+impl FileWatcher {
+    pub fn new() -> FileWatcher {
+        FileWatcher {
+            files: Vec::new(),
+            last_poll: Instant::now()
+        }
+    }
+    
+    // replaces the watched files, remembering their current state
+    pub fn watch(&mut self, files: Vec<PathBuf>) {
+        self.files = files
+            .into_iter()
+            .map(|path| {
+                let time = modified(&path);
+                (path, time)
+            })
+            .collect();
+    }
+    
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        
+        let mut changed = false;
+        for (path, time) in &mut self.files {
+            let new_time = modified(path);
+            if new_time != *time {
+                *time = new_time;
+                changed = true;
+            }
+        }
+        
+        changed
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
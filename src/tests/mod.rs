@@ -1,16 +1,27 @@
+use std::cell::RefCell;
 use std::fmt::Write;
+use std::rc::Rc;
 mod parser;
 mod ast;
 mod error;
 
 use std::fs::{read_dir, read_to_string, DirEntry};
 use std::io;
+use std::path::Path;
 use colored::Colorize;
 use crate::rtr::ast::node::EventTarget;
 use crate::rtr::{RTRModule};
+use crate::rtr::error::Error;
+use crate::rtr::modules::ModuleCache;
+use crate::rtr::runtime::shared::SharedValue;
+use crate::rtr::runtime::storage::{MemoryStore, Storage};
+use crate::rtr::runtime::timers::ManualClock;
 use crate::tests::ast::tokenise;
 use crate::tests::parser::{Code, CodePart, Parser, Test};
 
+// how far the clock moves each frame a test runs
+const FRAME_MS: f64 = 100.0;
+
 pub enum TestResultOutput {
     Pass,
     Fail { expected: Vec<String>, got: String }
@@ -46,11 +57,12 @@ pub fn run_test_file(file: &DirEntry) -> bool {
         println!("{}", format!("err in {}: {err}", file_name.display()).bright_red());
         false
     } else if let Ok(test) = test {
-        run_test(&test)
+        run_test(&test, &file.path())
     } else { false }
 }
 
-pub fn run_test(test: &Test) -> bool {
+// path is where the test file is, imports in it are found relative to it
+pub fn run_test(test: &Test, path: &Path) -> bool {
     let test_name = &test.name;
     
     let total = test.result.len();
@@ -59,7 +71,7 @@ pub fn run_test(test: &Test) -> bool {
     let mut out_text = String::new();
     
     for (idx, result) in test.result.iter().enumerate() {
-        let out = run_test_result(test, result.0.clone(), &result.1);
+        let out = run_test_result(test, path, result.0.clone(), &result.1);
         
         if let Err(err) = out {
             if total > 1 {
@@ -95,33 +107,74 @@ pub fn run_test(test: &Test) -> bool {
     passed == total
 }
 
-pub fn run_test_result(test: &Test, expected: Vec<String>, vars: &[String]) -> Result<TestResultOutput, String> {
-    let mut inst = RTRModule::new();
+pub fn run_test_result(test: &Test, path: &Path, expected: Vec<String>, vars: &[String]) -> Result<TestResultOutput, String> {
+    let clock = ManualClock::default();
+    // storage starts empty and stays in memory
+    let store = MemoryStore::default();
+    let storage = Rc::new(RefCell::new(Storage::new(store.clone())));
+    let modules = Rc::new(RefCell::new(ModuleCache::default()));
     
     let code = match test.code.clone() {
         Code::Expr(code) => format!("event(onload){{log(\n{}\n);}}", process_code(code, vars)),
         Code::Program(code) => process_code(code, vars)
     };
     
-    let parse_out = inst.parse(&code);
-    if let Err(err) = parse_out {
-        return Err(err.to_string());
+    let mut insts = Vec::new();
+    for _ in 0..test.scripts.max(1) {
+        let mut inst = RTRModule::new();
+        inst.path = Some(path.to_path_buf());
+        inst.timers.set_clock(clock.clone());
+        inst.storage = Rc::clone(&storage);
+        inst.modules = Rc::clone(&modules);
+        inst.inject();
+        
+        let parse_out = inst.parse(&code);
+        if let Err(err) = parse_out {
+            return Err(inst.diagnostic(&err).to_string());
+        }
+        insts.push(inst);
     }
     
-    let out = inst.run_event_target(&EventTarget::Global {
+    run_all(&mut insts, |inst| inst.run_event_target(&EventTarget::Global {
         name: String::from("onload")
-    });
+    }).map(|_| ()))?;
+    deliver_broadcasts(&mut insts)?;
+    
+    for _ in 0..test.frames {
+        clock.advance(FRAME_MS);
+        
+        run_all(&mut insts, |inst| inst.run_timers()
+            .and_then(|()| inst.resume())
+            .and_then(|()| inst.run_event_target_args(&EventTarget::Global {
+                name: String::from("onframe")
+            }, &[SharedValue::Num(FRAME_MS)]))
+            .map(|_| ()))?;
+        deliver_broadcasts(&mut insts)?;
+    }
     
-    if let Err(err) = out {
-        return Err(err.to_string());
+    // storage is opened again from what was saved, like the document being loaded again
+    let storage = Rc::new(RefCell::new(Storage::new(store)));
+    for inst in &mut insts {
+        inst.storage = Rc::clone(&storage);
     }
+    run_all(&mut insts, |inst| inst.run_event_target(&EventTarget::Global {
+        name: String::from("onreload")
+    }).map(|_| ()))?;
     
-    let mut str = inst.logs
+    let str = insts
         .iter()
+        .flat_map(|inst| &inst.logs)
         .map(crate::rtr::log::RTRLog::format)
         .collect::<Vec<_>>()
         .join(" ");
     
+    // everything run from the host should have taken what it put on the stack off again,
+    // including the modules that were called into
+    let modules = modules.borrow();
+    if let Some(inst) = insts.iter().chain(modules.loaded()).find(|inst| !inst.stack.is_empty()) {
+        return Err(format!("{} values were left on the stack", inst.stack.len()));
+    }
+    
     Ok(if expected.contains(&str) {
         TestResultOutput::Pass
     } else {
@@ -132,6 +185,38 @@ pub fn run_test_result(test: &Test, expected: Vec<String>, vars: &[String]) -> R
     })
 }
 
+// runs the same thing on every script in order
+fn run_all(insts: &mut [RTRModule], mut run: impl FnMut(&mut RTRModule) -> Result<(), Error>) -> Result<(), String> {
+    for inst in insts.iter_mut() {
+        if let Err(err) = run(inst) {
+            return Err(inst.diagnostic(&err).to_string());
+        }
+    }
+    
+    Ok(())
+}
+
+// a test is a document, so broadcasts go to every one of its scripts, the one that sent it too
+fn deliver_broadcasts(insts: &mut [RTRModule]) -> Result<(), String> {
+    for _ in 0..64 {
+        let broadcasts: Vec<_> = insts
+            .iter_mut()
+            .flat_map(RTRModule::take_broadcasts)
+            .collect();
+        if broadcasts.is_empty() {
+            break;
+        }
+        
+        for broadcast in &broadcasts {
+            run_all(insts, |inst| inst.run_event_target_args(&EventTarget::Global {
+                name: broadcast.name.clone()
+            }, std::slice::from_ref(&broadcast.value)).map(|_| ()))?;
+        }
+    }
+    
+    Ok(())
+}
+
 pub fn run_tests() {
     
     let mut total: usize = 0;
@@ -153,7 +238,8 @@ pub fn run_tests() {
                 .collect::<Vec<_>>();
             
             queue.append(&mut category_dir);
-        } else {
+        } else if entry.path().extension().is_some_and(|ext| ext == "rtrtest") {
+            // other files are left alone, like the modules the import tests use
             let pass = run_test_file(&entry);
             if pass {
                 passed += 1;
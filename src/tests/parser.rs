@@ -9,7 +9,11 @@ pub struct Test {
     pub name: String,
     pub desc: Option<String>,
     pub code: Code,
-    pub result: Vec<(Vec<String>,Vec<String>)>
+    pub result: Vec<(Vec<String>,Vec<String>)>,
+    // how many frames to run after onload, for timers and onframe
+    pub frames: usize,
+    // how many scripts run the code, sharing imports and storage like the scripts of one document
+    pub scripts: usize
 }
 
 #[derive(Debug, Clone)]
@@ -130,6 +134,8 @@ impl Parser {
         let mut desc: Option<String> = None;
         let mut code: Option<Code> = None;
         let mut result: Vec<(Vec<String>, Vec<String>)> = Vec::new();
+        let mut frames: usize = 0;
+        let mut scripts: usize = 1;
         
         self.consume_whitespace();
         while !self.at_end() {
@@ -159,6 +165,23 @@ impl Parser {
                 continue;
             }
             
+            if self.peek() == "frames" {
+                self.consume();
+                self.consume_whitespace();
+                self.expect(TokenType::Colon)?;
+                self.consume_whitespace();
+                frames = self.expect_num()?.parse().unwrap_or(0);
+                continue;
+            }
+            if self.peek() == "scripts" {
+                self.consume();
+                self.consume_whitespace();
+                self.expect(TokenType::Colon)?;
+                self.consume_whitespace();
+                scripts = self.expect_num()?.parse().unwrap_or(1);
+                continue;
+            }
+            
             if self.peek() == "code" {
                 self.consume();
                 self.consume_whitespace();
@@ -250,6 +273,8 @@ impl Parser {
             desc,
             code: code.unwrap(),
             result,
+            frames,
+            scripts
         };
         
         Ok(test)
@@ -337,7 +362,11 @@ impl Parser {
         Ok(lines)
     }
     fn parse_code_part(&mut self) -> Maybe<CodePart> {
-        if self.peek() == TokenType::Dollar {
+        // a $ not followed by a number is just code, like in an rtr template string
+        if self.peek() == TokenType::Dollar
+            && let TokenType::Text(txt) = self.peek_amount(2).token_type
+            && is_numeric(&txt)
+        {
             self.consume();
             let idx: usize = self.expect_num()?.parse().unwrap_or(0);
             return Ok(CodePart::Var(idx));